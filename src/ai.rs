@@ -0,0 +1,202 @@
+/*
+ * A solver: it tries every legal swap on a copy of the board, cascades included, and scores it
+ * with the rules of the game. For the hints, the autoplay, and to see what a change of the
+ * scoring rules does to the games.
+ */
+
+use crate::engine::{self, GameState, Mode, Rules};
+use crate::grid::{Grid, Move};
+use crate::score::{Breakdown, Score};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// How far the solver looks ahead.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+	/// Any legal move, for the screensaver that mustn't look too clever.
+	Random,
+	/// The move that scores the most, cascades included.
+	Greedy,
+	/// The move that scores the most on average over that many moves, its own included.
+	/// The refills aren't known in advance, so each move is tried on a few random ones.
+	/// It gets slow quickly: each move looked ahead multiplies the time by a hundred or so,
+	/// so it looks at MAX_DEPTH moves at most.
+	Expectimax(u8),
+}
+
+/// The most moves the solver looks at. It takes a quarter of a second a move at 3, several
+/// seconds at 4.
+pub const MAX_DEPTH: u8 = 3;
+
+impl Strategy {
+	/// How many moves it looks at, its own included.
+	fn depth(self) -> u8 {
+		match self {
+			Strategy::Random | Strategy::Greedy => 1,
+			Strategy::Expectimax(n) => n.clamp(1, MAX_DEPTH),
+		}
+	}
+}
+
+/// Picks the moves. It draws the refills it tries the moves on from its own seed, never from
+/// the seed of the grid, so it doesn't know the future any better than the player does.
+#[derive(Clone, Debug)]
+pub struct Solver {
+	pub strategy: Strategy,
+	/// How many random refills each move is tried on.
+	pub samples: u32,
+	rng: ChaCha8Rng,
+}
+
+impl Solver {
+	pub fn new(strategy: Strategy, seed: u64) -> Solver {
+		Solver { strategy, samples: 4, rng: ChaCha8Rng::seed_from_u64(seed) }
+	}
+
+	/// The best move, and the points it is expected to bring over the moves looked at.
+	/// None if the board is deadlocked. Nothing is expected of a random move, its value is 0.
+	pub fn best_move(&mut self, grid: &Grid) -> Option<(Move, f64)> {
+		if self.strategy == Strategy::Random {
+			return grid.legal_moves().choose(&mut self.rng).map(|&m| (m, 0.0));
+		}
+		let depth = self.strategy.depth();
+		return self.search(grid, depth);
+	}
+
+	fn search(&mut self, grid: &Grid, depth: u8) -> Option<(Move, f64)> {
+		let mut best: Option<(Move, f64)> = None;
+		for m in grid.legal_moves() {
+			let mut total = 0.0;
+			for _ in 0..self.samples {
+				let mut g = grid.clone();
+				g.reseed(self.rng.gen());
+				total += simulate(&mut g, m).points as f64;
+				// a board left without any move brings nothing more, the game would shuffle it.
+				if depth > 1 {
+					total += self.search(&g, depth - 1).map_or(0.0, |(_, v)| v);
+				}
+			}
+			let value = total / f64::from(self.samples.max(1));
+			if best.map_or(true, |(_, v)| value > v) {
+				best = Some((m, value));
+			}
+		}
+		return best;
+	}
+}
+
+/// Plays a move on the grid, with its whole cascade, and returns what it scored.
+/// The move has to be legal.
+pub fn simulate(grid: &mut Grid, (a, b): Move) -> Breakdown {
+	let mut score = Score::default();
+	grid.permute(a, b);
+	let mut swapped = vec![a, b];
+	for level in 0.. {
+		let Some(crush) = grid.crush(&swapped) else {
+			break;
+		};
+		score.crush(level, &crush);
+		swapped.clear();
+	}
+	return score.end_move();
+}
+
+/// The move to show as a hint. Always the same on the same board.
+pub fn hint(grid: &Grid) -> Option<Move> {
+	let mut solver = Solver::new(Strategy::Greedy, grid.seed() ^ grid.rng_position() as u64);
+	return solver.best_move(grid).map(|(m, _)| m);
+}
+
+/// Plays games of moves mode with the solver, without any interface, and returns their scores.
+/// The games are played on the seeds from the one of the rules on, or from 0, so that
+/// two runs play the same boards.
+pub fn benchmark(rules: &Rules, strategy: Strategy, games: u32) -> Result<Vec<u64>, &'static str> {
+	let mut scores = Vec::new();
+	for i in 0..games {
+		let seed = rules.seed.unwrap_or(0).wrapping_add(u64::from(i));
+		let rules = Rules { mode: Mode::Moves, target: u64::MAX, seed: Some(seed), ..rules.clone() };
+		let mut state = GameState::new(&rules)?;
+		let mut solver = Solver::new(strategy, seed);
+		while state.over().is_none() {
+			let Some((m, _)) = solver.best_move(state.grid()) else {
+				break;
+			};
+			state.apply(engine::Action::Swap(m.0, m.1))?;
+		}
+		scores.push(state.score().total());
+	}
+	return Ok(scores);
+}
+
+pub fn parse_strategy(s: &str) -> Result<Strategy, String> {
+	match s {
+		"random" => Ok(Strategy::Random),
+		"greedy" => Ok(Strategy::Greedy),
+		_ => match s.parse::<u8>() {
+			Ok(n @ 1..=MAX_DEPTH) => Ok(Strategy::Expectimax(n)),
+			_ => Err(format!("unknown strategy '{}', expected random, greedy or a number of moves from 1 to {}", s, MAX_DEPTH)),
+		},
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::grid::Gem::*;
+
+	#[test]
+	fn simulation() {
+		// the simulation plays the move like the game does.
+		let rules = Rules { seed: Some(11), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		let mut grid = state.grid().clone();
+		let m = grid.legal_moves()[0];
+		let b = simulate(&mut grid, m);
+		state.apply(engine::Action::Swap(m.0, m.1)).unwrap();
+		assert!(b.points > 0);
+		assert_eq!(b.points, state.score().total());
+		assert_eq!(grid.cells, state.grid().cells);
+	}
+
+	#[test]
+	fn solver() {
+		// Several swaps line up three gems, but moving the blue gem down lines up five blues.
+		let grid = Grid::from(ndarray::array![
+			[Red,   Red,    Yellow, Red,    Cyan],
+			[Green, Yellow, Blue,   Yellow, Green],
+			[Blue,  Blue,   Red,    Blue,   Blue],
+		]);
+		let mut greedy = Solver::new(Strategy::Greedy, 1);
+		let (m, value) = greedy.best_move(&grid).unwrap();
+		assert_eq!(m, ((1, 2), (2, 2)));
+		assert!(value > 0.0);
+
+		let mut lookahead = Solver::new(Strategy::Expectimax(2), 1);
+		lookahead.samples = 2;
+		let (m, _) = lookahead.best_move(&grid).unwrap();
+		assert!(grid.legal_moves().contains(&m));
+		let (m, _) = Solver::new(Strategy::Random, 1).best_move(&grid).unwrap();
+		assert!(grid.legal_moves().contains(&m));
+		assert_eq!(hint(&grid), hint(&grid));
+
+		let rules = Rules { moves: 3, ..Default::default() };
+		let scores = benchmark(&rules, Strategy::Greedy, 2).unwrap();
+		assert_eq!(scores.len(), 2);
+		assert!(scores.iter().all(|&s| s > 0));
+		assert_eq!(benchmark(&rules, Strategy::Greedy, 2).unwrap(), scores);
+	}
+
+	#[test]
+	fn strategies() {
+		assert_eq!(parse_strategy("greedy"), Ok(Strategy::Greedy));
+		assert_eq!(parse_strategy("random"), Ok(Strategy::Random));
+		assert_eq!(parse_strategy("2"), Ok(Strategy::Expectimax(2)));
+		assert_eq!(parse_strategy("3"), Ok(Strategy::Expectimax(MAX_DEPTH)));
+		assert!(parse_strategy("0").is_err());
+		assert!(parse_strategy("4").is_err());
+		assert_eq!(Strategy::Expectimax(9).depth(), MAX_DEPTH);
+		assert!(parse_strategy("deep").is_err());
+	}
+}
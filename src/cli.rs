@@ -0,0 +1,261 @@
+/*
+ * Command-line interface: turns the arguments of the program into a game::Config.
+ */
+
+use crate::ai;
+use crate::engine::Mode;
+use crate::frontend;
+use crate::game::{Config, Difficulty};
+use crate::grid::{MIN_COLOURS, MAX_COLOURS};
+use crate::keys;
+use crate::term::Theme;
+
+use std::time::Duration;
+
+pub const HELP: &str = "\
+A match-the-three game in the terminal.
+
+Usage: bejeweled-term [OPTIONS]
+
+Options:
+  -d, --difficulty <D>    Preset of board size and colours: easy (8×8, 5 colours),
+                          normal (8×6, 7 colours) or hard (6×5, 7 colours).
+                          The options below take precedence. [default: normal]
+  -W, --width <N>         Number of columns of the board, from 4 to 255.
+  -H, --height <N>        Number of lines of the board, from 4 to 255.
+  -c, --colours <N>       Number of colours of gems, from 4 to 7.
+  -S, --seed <SEED>       Seed of the random number generator, to replay a game.
+                          [default: a random one]
+  -m, --mode <MODE>       Game mode: endless, blitz to score as much as possible
+                          in a minute, moves to reach a target score in a given
+                          number of swaps, or levels to climb levels of growing
+                          difficulty. [default: endless]
+      --moves <N>         Number of swaps in moves mode. [default: 20]
+      --target <N>        Score to reach in moves mode. [default: 1000]
+  -s, --speed <SPEED>     Speed of the animations: slow, normal, fast, or how long
+                          each step of a cascade takes in milliseconds. [default: normal]
+  -t, --theme <THEME>     Look of the gems: classic, or letters to print the initial
+                          of each colour on the gems. [default: classic]
+      --frontend <F>      What to draw with: ncurses, or ansi for plain escape
+                          sequences, where ncurses isn't there. [default: ncurses,
+                          or ansi in a build without ncurses]
+  -k, --keys <PRESET>     Key bindings: azerty (Z/Q/S/D to swap, W to quit),
+                          qwerty (W/A/S/D to swap, X to quit), vim (h/j/k/l to move,
+                          H/J/K/L to swap, q to quit) or arrows (shift and an arrow
+                          to swap, q to quit). [default: azerty]
+      --hint-delay <S>    Show a hint after S seconds without a key pressed,
+                          0 to never show one on your own. [default: 10]
+      --name <NAME>       Name written in the high scores. [default: $USER]
+      --scores            Print the high scores of every mode and board size, and exit.
+      --autoplay          Let the computer play until a key is pressed, one move
+                          per step of the --speed.
+      --ai <STRATEGY>     How the computer picks its moves: random, greedy for the
+                          one that scores the most, or how many moves to look
+                          ahead, from 1 to 3, slower and slower. [default: greedy]
+      --benchmark <N>     Let the computer play N games of moves mode, from the seed
+                          on, and print their scores. [default seed: 0]
+  -n, --new               Start a new game, even if one was saved. Picking the board
+                          with the options above, or its --seed, does too.
+  -h, --help              Print this help and exit.
+";
+
+/// Parses the arguments, without the name of the program.
+/// Returns None if the help was asked for, in which case the game shall not be started.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Config>, String> {
+	let mut conf = Config::default();
+	let mut args = args.into_iter();
+	// These are applied on top of the difficulty, whatever the order of the arguments.
+	let mut difficulty = Difficulty::Normal;
+	let (mut width, mut height, mut colours) = (None, None, None);
+	// A board asked for on the command line isn't the one of the saved game.
+	let mut board = false;
+
+	while let Some(arg) = args.next() {
+		// Accept both "--width 8" and "--width=8".
+		let (name, inline_value) = match arg.split_once('=') {
+			Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+			_ => (arg.clone(), None),
+		};
+		let mut value = || -> Result<String, String> {
+			inline_value.clone()
+			            .or_else(|| args.next())
+			            .ok_or_else(|| format!("the option '{}' needs a value", name))
+		};
+
+		match name.as_str() {
+			"-h" | "--help" => return Ok(None),
+			"-n" | "--new" => conf.resume = false,
+			"--scores" => conf.show_scores = true,
+			"--autoplay" => conf.autoplay = true,
+			"--name" => conf.name = value()?,
+			"--ai" => conf.ai = ai::parse_strategy(&value()?)?,
+			"--benchmark" => {
+				let v = value()?;
+				conf.benchmark = Some(v.parse().ok().filter(|&n| n != 0)
+				                       .ok_or_else(|| format!("invalid number of games '{}', expected a positive integer", v))?);
+			}
+			"-d" | "--difficulty" => {
+				difficulty = parse_difficulty(&value()?)?;
+				board = true;
+			}
+			"-W" | "--width" => {
+				width = Some(parse_size(&value()?, "width")?);
+				board = true;
+			}
+			"-H" | "--height" => {
+				height = Some(parse_size(&value()?, "height")?);
+				board = true;
+			}
+			"-c" | "--colours" | "--colors" => {
+				colours = Some(parse_colours(&value()?)?);
+				board = true;
+			}
+			"-S" | "--seed" => {
+				let v = value()?;
+				conf.seed = Some(v.parse().map_err(|_| format!("invalid seed '{}', expected a positive integer", v))?);
+				board = true;
+			}
+			"-m" | "--mode" => conf.mode = parse_mode(&value()?)?,
+			"-s" | "--speed" => conf.animation_delay = parse_speed(&value()?)?,
+			"-t" | "--theme" => conf.theme = parse_theme(&value()?)?,
+			"--frontend" => conf.frontend = frontend::parse_kind(&value()?)?,
+			"-k" | "--keys" => conf.keys = keys::parse_preset(&value()?)?,
+			"--hint-delay" => {
+				let v = value()?;
+				let secs: u64 = v.parse().map_err(|_| format!("invalid hint delay '{}', expected a number of seconds", v))?;
+				conf.hint_delay = if secs == 0 {None} else {Some(Duration::from_secs(secs))};
+			}
+			"--moves" => {
+				let v = value()?;
+				conf.moves = v.parse().ok().filter(|&n| n != 0)
+				              .ok_or_else(|| format!("invalid number of moves '{}', expected a positive integer", v))?;
+			}
+			"--target" => {
+				let v = value()?;
+				conf.target = v.parse().map_err(|_| format!("invalid target '{}', expected a positive integer", v))?;
+			}
+			_ => return Err(format!("unknown argument '{}'", arg)),
+		}
+	}
+
+	difficulty.apply(&mut conf);
+	conf.width = width.unwrap_or(conf.width);
+	conf.height = height.unwrap_or(conf.height);
+	conf.colours = colours.unwrap_or(conf.colours);
+	if board {
+		conf.resume = false;
+	}
+	return Ok(Some(conf));
+}
+
+fn parse_difficulty(s: &str) -> Result<Difficulty, String> {
+	match s {
+		"easy" => Ok(Difficulty::Easy),
+		"normal" => Ok(Difficulty::Normal),
+		"hard" => Ok(Difficulty::Hard),
+		_ => Err(format!("unknown difficulty '{}', expected easy, normal or hard", s)),
+	}
+}
+
+fn parse_colours(s: &str) -> Result<u8, String> {
+	match s.parse::<u8>() {
+		Ok(n) if (MIN_COLOURS..=MAX_COLOURS).contains(&n) => Ok(n),
+		_ => Err(format!("invalid number of colours '{}', expected a number from {} to {}", s, MIN_COLOURS, MAX_COLOURS)),
+	}
+}
+
+/// The board is at least 4×4: the smaller ones can't always be dealt without a match and
+/// with a move to play.
+fn parse_size(s: &str, what: &str) -> Result<u8, String> {
+	match s.parse::<u8>() {
+		Ok(n) if n >= 4 => Ok(n),
+		_ => Err(format!("invalid {} '{}', expected a number from 4 to 255", what, s)),
+	}
+}
+
+fn parse_mode(s: &str) -> Result<Mode, String> {
+	Mode::from_name(s).ok_or_else(|| format!("unknown mode '{}', expected endless, blitz, moves or levels", s))
+}
+
+fn parse_speed(s: &str) -> Result<Duration, String> {
+	match s {
+		"slow" => Ok(Duration::from_millis(1500)),
+		"normal" => Ok(Duration::from_millis(1000)),
+		"fast" => Ok(Duration::from_millis(300)),
+		_ => s.parse::<u64>()
+		      .map(Duration::from_millis)
+		      .map_err(|_| format!("invalid speed '{}', expected slow, normal, fast or a number of milliseconds", s)),
+	}
+}
+
+fn parse_theme(s: &str) -> Result<Theme, String> {
+	match s {
+		"classic" => Ok(Theme::Classic),
+		"letters" => Ok(Theme::Letters),
+		_ => Err(format!("unknown theme '{}', expected classic or letters", s)),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn parse_str(s: &str) -> Result<Option<Config>, String> {
+		parse(s.split_whitespace().map(String::from))
+	}
+
+	#[test]
+	fn options() {
+		let conf = parse_str("").unwrap().unwrap();
+		assert_eq!((conf.height, conf.width), (8, 6));
+		assert!(conf.resume);
+
+		let conf = parse_str("-W 10 --height=4 --speed fast -t letters --hint-delay 0 -n").unwrap().unwrap();
+		assert_eq!((conf.height, conf.width), (4, 10));
+		assert_eq!(conf.animation_delay, Duration::from_millis(300));
+		assert_eq!(conf.theme, Theme::Letters);
+		assert_eq!(conf.hint_delay, None);
+		assert!(!conf.resume);
+
+		let conf = parse_str("--speed=250 --mode endless --seed 42").unwrap().unwrap();
+		assert_eq!(conf.animation_delay, Duration::from_millis(250));
+		assert_eq!(conf.seed, Some(42));
+		assert!(!conf.resume);
+		assert_eq!(conf.mode, Mode::Endless);
+		assert_eq!(parse_str("-m blitz").unwrap().unwrap().mode, Mode::Blitz);
+		assert_eq!(parse_str("--keys vim").unwrap().unwrap().keys, keys::Preset::Vim);
+		assert_eq!(parse_str("--frontend=ansi").unwrap().unwrap().frontend, frontend::Kind::Ansi);
+		let conf = parse_str("--name=Ada --scores").unwrap().unwrap();
+		assert_eq!(conf.name, "Ada");
+		assert!(conf.show_scores);
+		let conf = parse_str("--autoplay --ai random").unwrap().unwrap();
+		assert!(conf.autoplay);
+		assert_eq!(conf.ai, ai::Strategy::Random);
+		let conf = parse_str("--benchmark 50 --ai 2").unwrap().unwrap();
+		assert_eq!((conf.benchmark, conf.ai), (Some(50), ai::Strategy::Expectimax(2)));
+		let conf = parse_str("-m moves --moves 15 --target=2500").unwrap().unwrap();
+		assert_eq!((conf.mode, conf.moves, conf.target), (Mode::Moves, 15, 2500));
+
+		assert!(parse_str("--width 4 --help").unwrap().is_none());
+
+		let conf = parse_str("--width 9 -d easy").unwrap().unwrap();
+		assert_eq!((conf.height, conf.width, conf.colours), (8, 9, 5));
+		assert!(!conf.resume);
+		assert!(parse_str("-m blitz --keys vim").unwrap().unwrap().resume);
+		let conf = parse_str("-c 4 --difficulty hard").unwrap().unwrap();
+		assert_eq!((conf.height, conf.width, conf.colours), (6, 5, 4));
+		let conf = parse_str("--colors=6").unwrap().unwrap();
+		assert_eq!((conf.height, conf.width, conf.colours), (8, 6, 6));
+	}
+
+	#[test]
+	fn errors() {
+		for bad in &["--width 1", "--height 3", "--height 256", "-W", "--width=abc", "--speed warp",
+		             "--theme neon", "--seed -1", "--mode zen", "--colours 3", "--colors 8",
+		             "-d insane", "--moves 0", "-k dvorak", "--target lots", "--frontend gtk", "--ai 9",
+		             "--benchmark 0", "extra"] {
+			assert!(parse_str(bad).is_err(), "{:?}", bad);
+		}
+		assert_eq!(parse_str("-H 0").unwrap_err(), "invalid height '0', expected a number from 4 to 255");
+	}
+}
@@ -0,0 +1,720 @@
+/*
+ * The rules of the game, without any interface: actions come in, events come out.
+ *
+ * Nothing in here draws, sleeps, reads the clock or writes a word for the player, so the same
+ * rules can drive the terminal, the tests, a bot or a replay.
+ */
+
+use crate::ai;
+use crate::grid::{Crush, Grid, Move};
+use crate::score::{Breakdown, Score};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// How long a game of blitz lasts.
+const BLITZ_TIME: Duration = Duration::from_secs(60);
+// The time given back to the player by each step of a cascade, in blitz.
+const CASCADE_BONUS: Duration = Duration::from_secs(2);
+// How many moves can be undone.
+const HISTORY: usize = 100;
+
+/// The rules of the game, and when it ends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+	/// Play as long as you like.
+	Endless,
+	/// Score as much as possible in a minute. Cascades give some extra time.
+	Blitz,
+	/// Reach the target score in a given number of swaps.
+	Moves,
+	/// Climb the levels, each one on a harder board than the one before.
+	Levels,
+}
+
+impl Mode {
+	pub const ALL: [Mode; 4] = [Mode::Endless, Mode::Blitz, Mode::Moves, Mode::Levels];
+
+	/// The name of the mode, on the command line and in the files.
+	pub fn name(self) -> &'static str {
+		match self {
+			Mode::Endless => "endless",
+			Mode::Blitz => "blitz",
+			Mode::Moves => "moves",
+			Mode::Levels => "levels",
+		}
+	}
+
+	pub fn from_name(s: &str) -> Option<Mode> {
+		Mode::ALL.iter().copied().find(|m| m.name() == s)
+	}
+}
+
+/// A level of the levels mode: the board it is played on, and the score to reach to move on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Level {
+	/// Starts from 1.
+	pub number: u32,
+	pub height: u8,
+	pub width: u8,
+	pub colours: u8,
+	/// The total score that takes the player to the next level.
+	pub target: u64,
+}
+
+impl Level {
+	/// The n-th level, from 1. The colours come first, then the board shrinks.
+	/// Past the table, the levels all have the smallest board and a higher target.
+	pub fn nth(n: u32) -> Level {
+		const LEVELS: [(u8, u8, u8, u64); 5] = [
+			(8, 8, 5, 500),
+			(8, 8, 6, 1500),
+			(8, 7, 6, 3000),
+			(8, 7, 7, 5000),
+			(7, 6, 7, 8000),
+		];
+		let n = n.max(1);
+		let (height, width, colours, target) = match LEVELS.get(n as usize - 1) {
+			Some(&l) => l,
+			None => (6, 5, 7, 8000 + 4000 * u64::from(n - LEVELS.len() as u32)),
+		};
+		return Level { number: n, height, width, colours, target };
+	}
+
+	/// Sets the board size and number of colours of the rules.
+	pub fn apply(self, rules: &mut Rules) {
+		rules.height = self.height;
+		rules.width = self.width;
+		rules.colours = self.colours;
+	}
+}
+
+/// What the rules need to know of the configuration: the board, and the mode it is played in.
+#[derive(Clone, Debug)]
+pub struct Rules {
+	pub width: u8,
+	pub height: u8,
+	/// How many different colours of gems are on the board, from grid::MIN_COLOURS to MAX_COLOURS.
+	pub colours: u8,
+	/// Seed of the random number generator of the grid. None plays on the boards of seed 0.
+	pub seed: Option<u64>,
+	pub mode: Mode,
+	/// How many swaps the player has, in moves mode.
+	pub moves: u32,
+	/// The score to reach with them.
+	pub target: u64,
+}
+
+impl Default for Rules {
+	fn default() -> Self {
+		Rules {
+			width: 6,
+			height: 8,
+			colours: crate::grid::MAX_COLOURS,
+			seed: None,
+			mode: Mode::Endless,
+			moves: 20,
+			target: 1000,
+		}
+	}
+}
+
+/// How a game ended, it sets the colour of the game over banner.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+	Won,
+	Lost,
+	/// Neither won nor lost, the game simply came to its end.
+	Finished,
+}
+
+/// What ended a game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ending {
+	/// The clock of blitz ran out.
+	TimeUp,
+	/// The score of moves mode was reached, or the moves ran out before.
+	TargetReached,
+	OutOfMoves,
+}
+
+impl Ending {
+	pub fn outcome(self) -> Outcome {
+		match self {
+			Ending::TimeUp => Outcome::Finished,
+			Ending::TargetReached => Outcome::Won,
+			Ending::OutOfMoves => Outcome::Lost,
+		}
+	}
+}
+
+/// Why an action was refused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Refusal {
+	GameOver,
+	/// The gems of a swap have to be next to each other, and on the board.
+	NotNeighbours,
+	NothingToUndo,
+	NothingToRedo,
+}
+
+/// What the status bar is made of. The Term words it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Status {
+	pub score: u64,
+	pub best_combo: u32,
+	pub seed: u64,
+	/// The points of the last move, if there was one.
+	pub last: Option<Breakdown>,
+	/// The time left, in blitz.
+	pub time: Option<Duration>,
+	/// The swaps left, and the score to reach, in moves mode.
+	pub moves: Option<(u32, u64)>,
+	/// The current level, in levels mode.
+	pub level: Option<Level>,
+}
+
+/// What the player does to the game. Unlike the actions of the keys, they don't depend on
+/// where the cursor is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+	/// Swap two neighbouring gems.
+	Swap((usize, usize), (usize, usize)),
+	/// Look for the best move.
+	Hint,
+	/// Take back the last move, or play again the last move taken back.
+	Undo,
+	Redo,
+	/// Let some time pass. Only the clock of blitz cares.
+	Tick(Duration),
+	/// Start over on a fresh board.
+	Restart,
+}
+
+/// What came out of an action, in the order it happened.
+#[derive(Clone, Debug)]
+pub enum Event {
+	/// Two gems were swapped, the grid is the board with the gems swapped.
+	/// If they didn't match, they went back to their place right away.
+	Swapped { grid: Box<Grid>, a: (usize, usize), b: (usize, usize), matched: bool },
+	/// A step of the cascade: the board before and after it, and the status bar after it.
+	Crushed { before: Box<Grid>, after: Box<Grid>, crush: Crush, status: Box<Status> },
+	/// The cascade went on for another step. Blitz gives some time back for it.
+	Combo { combo: u32, bonus: Option<Duration> },
+	/// The move is over, and that's what it was worth.
+	Scored(Breakdown),
+	/// The board was left without any move, it was shuffled.
+	Reshuffled,
+	/// The best move, if there's one.
+	Hint(Option<Move>),
+	/// The board went back one move, or forth.
+	Undone,
+	Redone,
+	/// The action can't be done, and why.
+	Refused(Refusal),
+	/// On to the next level, on a brand new board.
+	LevelUp(Level),
+	/// The game is over.
+	Over(Ending),
+	/// A new game started.
+	Restarted,
+}
+
+/// What ends the current game, or moves it on, depending on the mode.
+#[derive(Clone, Copy, Debug)]
+struct Limits {
+	/// The time left, in blitz.
+	time: Option<Duration>,
+	/// The swaps left to the player, and the score to reach with them, in moves mode.
+	moves: Option<(u32, u64)>,
+	/// The current level, in levels mode.
+	level: Option<Level>,
+}
+
+impl Limits {
+	/// The limits of a game that starts now.
+	fn new(rules: &Rules) -> Limits {
+		let mut limits = Limits { time: None, moves: None, level: None };
+		match rules.mode {
+			Mode::Endless => (),
+			Mode::Blitz => limits.time = Some(BLITZ_TIME),
+			Mode::Moves => limits.moves = Some((rules.moves, rules.target)),
+			Mode::Levels => limits.level = Some(Level::nth(1)),
+		}
+		return limits;
+	}
+}
+
+/// The state of the game before a move, to undo it.
+/// The grid carries its random number generator, so that the refills come out the same.
+#[derive(Clone, Debug)]
+struct Snapshot {
+	grid: Grid,
+	score: Score,
+	limits: Limits,
+}
+
+/// A game in progress, and its rules.
+#[derive(Clone, Debug)]
+pub struct GameState {
+	// In levels mode, the board is the one of the level, whatever the rules said.
+	rules: Rules,
+	grid: Grid,
+	score: Score,
+	limits: Limits,
+	// The moves to undo, the latest at the back, and the moves undone, to redo them.
+	undo: VecDeque<Snapshot>,
+	redo: Vec<Snapshot>,
+	over: Option<Ending>,
+	// What happened when the game started, handed out with the first action.
+	pending: Vec<Event>,
+	// How many times the game started over, each time on another board.
+	restarts: u64,
+}
+
+/// A fresh grid of the size and colours of the rules, without any match on it and with
+/// at least one move to play. If it had to be reshuffled to get a move, `events` says so.
+pub fn new_grid(rules: &Rules, seed: u64, events: &mut Vec<Event>) -> Result<Grid, &'static str> {
+	let mut grid = Grid::new_rand(rules.height, rules.width, rules.colours, seed)?;
+
+	// clean the grid of any match
+	for i in 0..64 {
+		if i == 63 {
+			return Err("Couldn't clean the grid of its matches.");
+		}
+		let (v, _) = grid.get_all_matches();
+		if v.is_empty() {
+			break;
+		}
+		grid.gravity(&v);
+	}
+	if grid.legal_moves().is_empty() {
+		grid.shuffle()?;
+		events.push(Event::Reshuffled);
+	}
+	return Ok(grid);
+}
+
+impl GameState {
+
+	/// A new game with the given rules. Its board comes from the seed of the
+	/// rules, or from 0 if there's none: nothing in here is drawn at random.
+	pub fn new(rules: &Rules) -> Result<GameState, &'static str> {
+		return GameState::start(rules, 0);
+	}
+
+	/// A new game, after that many restarts.
+	fn start(rules: &Rules, restarts: u64) -> Result<GameState, &'static str> {
+		let mut rules = rules.clone();
+		let limits = Limits::new(&rules);
+		if let Some(level) = limits.level {
+			level.apply(&mut rules);
+		}
+		let mut pending = Vec::new();
+		let grid = new_grid(&rules, board_seed(&rules, restarts, limits.level), &mut pending)?;
+		return Ok(GameState {
+			rules,
+			grid,
+			score: Score::default(),
+			limits,
+			undo: VecDeque::with_capacity(HISTORY),
+			redo: Vec::new(),
+			over: None,
+			pending,
+			restarts,
+		});
+	}
+
+	/// Carries on with a saved game, in place of the current one.
+	pub fn resume(&mut self, grid: Grid, score: Score) {
+		self.grid = grid;
+		self.score = score;
+		self.undo.clear();
+		self.redo.clear();
+		self.pending.clear();
+	}
+
+	pub fn grid(&self) -> &Grid {
+		&self.grid
+	}
+
+	pub fn score(&self) -> &Score {
+		&self.score
+	}
+
+	/// What ended the game, if it did.
+	pub fn over(&self) -> Option<Ending> {
+		self.over
+	}
+
+	/// The score to reach, in moves mode.
+	pub fn target(&self) -> Option<u64> {
+		self.limits.moves.map(|(_, target)| target)
+	}
+
+	/// Plays an action, and tells what happened. Most of the refused actions are events,
+	/// the errors are the few things that can't go on, like a board that can't be shuffled.
+	pub fn apply(&mut self, action: Action) -> Result<Vec<Event>, &'static str> {
+		let mut events = std::mem::take(&mut self.pending);
+		if self.over.is_some() && !matches!(action, Action::Restart | Action::Tick(_)) {
+			events.push(Event::Refused(Refusal::GameOver));
+			return Ok(events);
+		}
+		match action {
+			Action::Swap(a, b) => self.swap(a, b, &mut events)?,
+			Action::Hint => events.push(Event::Hint(ai::hint(&self.grid))),
+			// go back and forth in the history, the clock of blitz keeps running though.
+			Action::Undo | Action::Redo => {
+				let current = self.snapshot();
+				let snapshot = if action == Action::Undo {
+					self.undo.pop_back().inspect(|_| self.redo.push(current))
+				} else {
+					self.redo.pop().inspect(|_| self.undo.push_back(current))
+				};
+				let Some(snapshot) = snapshot else {
+					events.push(Event::Refused(if action == Action::Undo {Refusal::NothingToUndo} else {Refusal::NothingToRedo}));
+					return Ok(events);
+				};
+				self.grid = snapshot.grid;
+				self.score = snapshot.score;
+				self.limits = Limits { time: self.limits.time, ..snapshot.limits };
+				events.push(if action == Action::Undo {Event::Undone} else {Event::Redone});
+			}
+			Action::Tick(elapsed) => {
+				if let Some(time) = self.limits.time.as_mut() {
+					*time = time.saturating_sub(elapsed);
+					if time.is_zero() && self.over.is_none() {
+						self.end(Ending::TimeUp, &mut events);
+					}
+				}
+			}
+			Action::Restart => {
+				*self = GameState::start(&self.rules, self.restarts + 1)?;
+				events.push(Event::Restarted);
+				events.append(&mut self.pending);
+			}
+		}
+		return Ok(events);
+	}
+
+	fn swap(&mut self, a: (usize, usize), b: (usize, usize), events: &mut Vec<Event>) -> Result<(), &'static str> {
+		let (lines, cols) = self.grid.size();
+		let neighbours = a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1;
+		if !neighbours || a.0.max(b.0) >= lines || a.1.max(b.1) >= cols {
+			events.push(Event::Refused(Refusal::NotNeighbours));
+			return Ok(());
+		}
+
+		let before = self.snapshot();
+		self.grid.permute(a, b);
+		let matched = self.grid.check_matches(a, b);
+		events.push(Event::Swapped { grid: Box::new(self.grid.clone()), a, b, matched });
+		if !matched {
+			self.grid.permute(a, b);
+			return Ok(());
+		}
+
+		if self.undo.len() == HISTORY {
+			self.undo.pop_front();
+		}
+		self.undo.push_back(before);
+		self.redo.clear();
+		// Only the swaps that match count in moves mode.
+		if let Some((left, _)) = self.limits.moves.as_mut() {
+			*left -= 1;
+		}
+
+		// There, we can finally play. Update the grid, and then the new matches it formed.
+		let mut swapped = vec![a, b];
+		for lvl in 0.. {
+			let before = Box::new(self.grid.clone());
+			let Some(crush) = self.grid.crush(&swapped) else {
+				break;
+			};
+			self.score.crush(lvl, &crush);
+			events.push(Event::Crushed { before, after: Box::new(self.grid.clone()), crush, status: Box::new(self.status()) });
+			if lvl > 0 {
+				let bonus = self.limits.time.as_mut().map(|t| {
+					*t += CASCADE_BONUS;
+					CASCADE_BONUS
+				});
+				events.push(Event::Combo { combo: lvl + 1, bonus });
+			}
+			swapped.clear();
+		}
+
+		// the move is over, the status bar shows its points.
+		events.push(Event::Scored(self.score.end_move()));
+		// the cascade may have left the player without any move.
+		if self.grid.legal_moves().is_empty() {
+			self.grid.shuffle()?;
+			events.push(Event::Reshuffled);
+		}
+
+		match self.limits.moves {
+			Some((_, target)) if self.score.total() >= target => self.end(Ending::TargetReached, events),
+			Some((0, _)) => self.end(Ending::OutOfMoves, events),
+			_ => (),
+		}
+
+		// on to the next level, on a brand new board. The moves of the previous level were
+		// played on another board, possibly of another size: they can't be undone any more.
+		if let Some(level) = self.limits.level.filter(|l| self.score.total() >= l.target) {
+			let next = Level::nth(level.number + 1);
+			next.apply(&mut self.rules);
+			let mut shuffled = Vec::new();
+			self.grid = new_grid(&self.rules, board_seed(&self.rules, self.restarts, Some(next)), &mut shuffled)?;
+			self.limits.level = Some(next);
+			self.undo.clear();
+			self.redo.clear();
+			events.push(Event::LevelUp(next));
+			events.append(&mut shuffled);
+		}
+		return Ok(());
+	}
+
+	fn end(&mut self, ending: Ending, events: &mut Vec<Event>) {
+		self.over = Some(ending);
+		events.push(Event::Over(ending));
+	}
+
+	fn snapshot(&self) -> Snapshot {
+		Snapshot { grid: self.grid.clone(), score: self.score.clone(), limits: self.limits }
+	}
+
+	/// What the status bar shows: the score, the points of the last move and the seed, and
+	/// what is left of the time or of the moves, if the game is limited.
+	pub fn status(&self) -> Status {
+		return Status {
+			score: self.score.total(),
+			best_combo: self.score.best_combo(),
+			seed: self.grid.seed(),
+			last: self.score.last(),
+			time: self.limits.time,
+			moves: self.limits.moves,
+			level: self.limits.level,
+		};
+	}
+}
+
+/// The seed of a board: the one of the rules, moved on by the restarts and the levels,
+/// so that each new game and each level has a board of its own.
+fn board_seed(rules: &Rules, restarts: u64, level: Option<Level>) -> u64 {
+	let level = level.map_or(0, |l| u64::from(l.number) - 1);
+	return rules.seed.unwrap_or(0).wrapping_add(restarts << 16).wrapping_add(level);
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn snapshots() {
+		// Play the best move and its cascade.
+		fn play(grid: &mut Grid, score: &mut Score) {
+			let (a, b) = ai::hint(grid).unwrap();
+			grid.permute(a, b);
+			let mut swapped = vec![a, b];
+			let mut level = 0;
+			while let Some(crush) = grid.crush(&swapped) {
+				score.crush(level, &crush);
+				swapped.clear();
+				level += 1;
+			}
+			score.end_move();
+		}
+
+		let rules = Rules { seed: Some(2021), ..Default::default() };
+		let mut grid = new_grid(&rules, 2021, &mut Vec::new()).unwrap();
+		let mut score = Score::default();
+		play(&mut grid, &mut score);
+		let snapshot = Snapshot { grid: grid.clone(), score: score.clone(), limits: Limits::new(&rules) };
+
+		play(&mut grid, &mut score);
+		let mut restored = snapshot.clone();
+		play(&mut restored.grid, &mut restored.score);
+		// The same move on the restored grid brings the same refills.
+		assert_eq!(restored.grid.cells, grid.cells);
+		assert_eq!(restored.grid.rng_position(), grid.rng_position());
+		assert_eq!(restored.score.total(), score.total());
+		assert!(snapshot.score.total() < score.total());
+	}
+
+	#[test]
+	fn apply() {
+		let rules = Rules { seed: Some(7), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		let start = state.grid().cells.clone();
+
+		let (a, b) = ai::hint(state.grid()).unwrap();
+		let events = state.apply(Action::Swap(a, b)).unwrap();
+		assert!(matches!(events[0], Event::Swapped { matched: true, .. }));
+		assert!(matches!(events[1], Event::Crushed { .. }));
+		assert!(events.iter().any(|e| matches!(e, Event::Scored(b) if b.points > 0)));
+		let total = state.score().total();
+		let after = state.grid().cells.clone();
+
+		let events = state.apply(Action::Undo).unwrap();
+		assert!(matches!(events[..], [Event::Undone]));
+		assert_eq!(state.grid().cells, start);
+		assert_eq!(state.score().total(), 0);
+		assert!(matches!(state.apply(Action::Undo).unwrap()[..], [Event::Refused(_)]));
+		state.apply(Action::Redo).unwrap();
+		assert_eq!(state.grid().cells, after);
+		assert_eq!(state.score().total(), total);
+
+		assert!(matches!(state.apply(Action::Swap((0, 0), (2, 0))).unwrap()[..], [Event::Refused(_)]));
+		assert!(state.apply(Action::Tick(Duration::from_secs(3600))).unwrap().is_empty());
+		assert_eq!(state.over(), None);
+	}
+
+	#[test]
+	fn limits() {
+		// blitz: the clock runs out.
+		let rules = Rules { mode: Mode::Blitz, seed: Some(7), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		assert_eq!(state.status().time, Some(BLITZ_TIME));
+		assert!(state.apply(Action::Tick(BLITZ_TIME / 2)).unwrap().is_empty());
+		let events = state.apply(Action::Tick(BLITZ_TIME)).unwrap();
+		assert!(matches!(events[..], [Event::Over(Ending::TimeUp)]));
+		assert_eq!(state.over().map(Ending::outcome), Some(Outcome::Finished));
+		assert!(matches!(state.apply(Action::Hint).unwrap()[..], [Event::Refused(Refusal::GameOver)]));
+		assert!(matches!(state.apply(Action::Restart).unwrap()[..], [Event::Restarted]));
+		assert_eq!(state.over(), None);
+
+		// moves: a single swap, and an unreachable target.
+		let rules = Rules { mode: Mode::Moves, moves: 1, target: u64::MAX, seed: Some(7), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		let (a, b) = ai::hint(state.grid()).unwrap();
+		let events = state.apply(Action::Swap(a, b)).unwrap();
+		assert!(matches!(events.last(), Some(Event::Over(Ending::OutOfMoves))));
+		assert_eq!(state.target(), Some(u64::MAX));
+	}
+
+	#[test]
+	fn moves() {
+		let rules = Rules { mode: Mode::Moves, moves: 3, target: 1000, seed: Some(7), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		assert_eq!(state.status().moves, Some((3, 1000)));
+
+		// a swap that matches nothing doesn't count.
+		let (lines, cols) = state.grid().size();
+		let (a, b) = (0..lines).flat_map(|x| (1..cols).map(move |y| ((x, y - 1), (x, y))))
+			.find(|&(a, b)| {
+				let mut grid = state.grid().clone();
+				grid.permute(a, b);
+				!grid.check_matches(a, b)
+			}).unwrap();
+		assert!(matches!(state.apply(Action::Swap(a, b)).unwrap()[..], [Event::Swapped { matched: false, .. }]));
+		assert_eq!(state.status().moves, Some((3, 1000)));
+
+		// one that does, and the undo gives it back.
+		let (a, b) = ai::hint(state.grid()).unwrap();
+		state.apply(Action::Swap(a, b)).unwrap();
+		assert_eq!(state.status().moves, Some((2, 1000)));
+		state.apply(Action::Undo).unwrap();
+		assert_eq!(state.status().moves, Some((3, 1000)));
+
+		// the target is reached before the moves run out.
+		let rules = Rules { target: 1, ..rules };
+		let mut state = GameState::new(&rules).unwrap();
+		let (a, b) = ai::hint(state.grid()).unwrap();
+		let events = state.apply(Action::Swap(a, b)).unwrap();
+		assert!(matches!(events.last(), Some(Event::Over(Ending::TargetReached))));
+		assert_eq!(state.over().map(Ending::outcome), Some(Outcome::Won));
+		assert!(matches!(state.apply(Action::Undo).unwrap()[..], [Event::Refused(Refusal::GameOver)]));
+
+		// a restart gives all the moves back, on a new board.
+		let seed = state.grid().seed();
+		assert!(matches!(state.apply(Action::Restart).unwrap()[..], [Event::Restarted, ..]));
+		assert_eq!(state.over(), None);
+		assert_eq!(state.score().total(), 0);
+		assert_eq!(state.status().moves, Some((3, 1)));
+		assert_ne!(state.grid().seed(), seed);
+		assert!(matches!(state.apply(Action::Undo).unwrap()[..], [Event::Refused(Refusal::NothingToUndo)]));
+	}
+
+	#[test]
+	fn level_table() {
+		assert_eq!(Level::nth(0), Level::nth(1));
+		let mut previous = Level::nth(1);
+		for n in 2..20 {
+			let level = Level::nth(n);
+			assert_eq!(level.number, n);
+			assert!(level.target > previous.target);
+			assert!(level.colours >= previous.colours);
+			assert!(level.height * level.width <= previous.height * previous.width);
+			assert!((crate::grid::MIN_COLOURS..=crate::grid::MAX_COLOURS).contains(&level.colours));
+			previous = level;
+		}
+	}
+
+	#[test]
+	fn levels() {
+		let rules = Rules { mode: Mode::Levels, seed: Some(7), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		assert_eq!(state.grid().size(), (8, 8));
+		assert_eq!(state.status().level.map(|l| l.number), Some(1));
+		state.apply(Action::Restart).unwrap();
+		assert_eq!(state.grid().size(), (8, 8));
+		let first = state.grid().seed();
+
+		// play on to the next level, there's no going back to the board of the previous one.
+		let mut events = Vec::new();
+		while !events.iter().any(|e| matches!(e, Event::LevelUp(_))) {
+			let (a, b) = ai::hint(state.grid()).unwrap();
+			events = state.apply(Action::Swap(a, b)).unwrap();
+		}
+		assert_ne!(state.grid().seed(), first);
+		let (size, cells) = (state.grid().size(), state.grid().cells.clone());
+		assert!(matches!(state.apply(Action::Undo).unwrap()[..], [Event::Refused(_)]));
+		assert!(matches!(state.apply(Action::Redo).unwrap()[..], [Event::Refused(_)]));
+		assert_eq!(state.grid().size(), size);
+		assert_eq!(state.grid().cells, cells);
+
+		// the moves of the new level can be undone as usual.
+		let (a, b) = ai::hint(state.grid()).unwrap();
+		state.apply(Action::Swap(a, b)).unwrap();
+		assert!(matches!(state.apply(Action::Undo).unwrap()[..], [Event::Undone]));
+		assert_eq!(state.grid().cells, cells);
+		assert!(matches!(state.apply(Action::Redo).unwrap()[..], [Event::Redone]));
+		assert_eq!(state.grid().size(), size);
+	}
+
+	#[test]
+	fn seeds() {
+		// the same seed, the same board, and a new one on each restart.
+		let rules = Rules { seed: Some(7), ..Default::default() };
+		let mut state = GameState::new(&rules).unwrap();
+		let first = state.grid().clone();
+		assert_eq!(GameState::new(&rules).unwrap().grid().cells, first.cells);
+		state.apply(Action::Restart).unwrap();
+		assert_ne!(state.grid().seed(), first.seed());
+		assert_ne!(state.grid().cells, first.cells);
+		state.apply(Action::Restart).unwrap();
+		assert_ne!(state.grid().seed(), first.seed());
+
+		// nothing is drawn at random without a seed either.
+		let rules = Rules::default();
+		assert_eq!(GameState::new(&rules).unwrap().grid().cells, GameState::new(&rules).unwrap().grid().cells);
+	}
+
+	#[test]
+	fn start() {
+		// Small boards often come out without any move, the player is told when they're reshuffled.
+		let mut reshuffled = 0;
+		for seed in 0..50 {
+			let rules = Rules { width: 4, height: 4, seed: Some(seed), ..Default::default() };
+			let mut state = GameState::new(&rules).unwrap();
+			assert!(!state.grid().legal_moves().is_empty());
+			let events = state.apply(Action::Tick(Duration::ZERO)).unwrap();
+			if matches!(events[..], [Event::Reshuffled]) {
+				reshuffled += 1;
+			} else {
+				assert!(events.is_empty());
+			}
+			assert!(state.apply(Action::Tick(Duration::ZERO)).unwrap().is_empty());
+		}
+		assert!(reshuffled > 0);
+	}
+}
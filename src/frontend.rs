@@ -0,0 +1,234 @@
+/*
+ * What the Term draws on and reads the keys from: ncurses, or plain ANSI escape sequences.
+ *
+ * The Term lays out the board and runs the animations, the frontends only know how to put
+ * characters on the screen and how to read what the player types.
+ */
+
+#[cfg(feature = "ncurses")]
+mod curses;
+mod ansi;
+#[cfg(test)]
+pub mod memory;
+
+use crate::keys::Key;
+
+use std::time::Duration;
+
+// The characters of the line drawings. The ncurses frontend draws them with its own.
+pub const HLINE: char = '─';
+pub const VLINE: char = '│';
+pub const CHECKER: char = '▒';
+pub const DIAMOND: char = '◆';
+pub const LANTERN: char = '␋';
+pub const ULCORNER: char = '┌';
+pub const URCORNER: char = '┐';
+pub const LLCORNER: char = '└';
+pub const LRCORNER: char = '┘';
+
+/// The colours of the text, always on a black background.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Colour {
+	White,
+	Red,
+	Green,
+	Yellow,
+	Blue,
+	Magenta,
+	Cyan,
+}
+
+/// How a character looks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Style {
+	/// None is the default colour of the terminal.
+	pub colour: Option<Colour>,
+	pub bold: bool,
+	pub dim: bool,
+	/// Swap the colour of the text and of its background. A reversed space is a block of colour.
+	pub reverse: bool,
+}
+
+impl Style {
+	pub fn colour(colour: Colour) -> Style {
+		Style { colour: Some(colour), ..Default::default() }
+	}
+
+	pub fn bold(self) -> Style {
+		Style { bold: true, ..self }
+	}
+
+	pub fn dim(self) -> Style {
+		Style { dim: true, ..self }
+	}
+
+	pub fn reverse(self) -> Style {
+		Style { reverse: true, ..self }
+	}
+}
+
+/// A character on the screen, and how it looks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyph {
+	pub c: char,
+	pub style: Style,
+}
+
+impl Glyph {
+	pub fn new(c: char, style: Style) -> Glyph {
+		Glyph { c, style }
+	}
+}
+
+impl Default for Glyph {
+	fn default() -> Self {
+		Glyph { c: ' ', style: Style::default() }
+	}
+}
+
+/// Something the player did.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+	Key(Key),
+	/// The main button of the mouse went down, or up, over that line and column.
+	Mouse { line: i32, col: i32, pressed: bool },
+	PageUp,
+	PageDown,
+	/// The screen changed size, everything has to be drawn again.
+	Resize,
+	/// A key, or anything else, the game doesn't know about.
+	Unknown,
+}
+
+/// Where the Term draws, and where it reads the input from.
+///
+/// The lines and columns start from 0 at the top left corner. Whatever is drawn out of the
+/// screen is left out, so that the gems can slide in from above the board.
+pub trait Frontend {
+	/// How many lines and columns the screen has.
+	fn size(&self) -> (usize, usize);
+
+	/// Blank the whole screen.
+	fn erase(&mut self);
+
+	fn put(&mut self, line: i32, col: i32, glyph: Glyph);
+
+	/// Write some text from that place on. It is cut at the edge of the screen.
+	fn put_str(&mut self, line: i32, col: i32, text: &str, style: Style) {
+		for (i, c) in text.chars().enumerate() {
+			self.put(line, col + i as i32, Glyph::new(c, style));
+		}
+	}
+
+	/// Show what was drawn since the last refresh.
+	fn refresh(&mut self);
+
+	/// Wait for the player to do something, but not longer than `timeout`, if there's one.
+	/// Returns None if the time ran out.
+	fn input(&mut self, timeout: Option<Duration>) -> Option<Input>;
+
+	/// Forget the keys typed in advance.
+	fn flush_input(&mut self);
+
+	/// Wait a bit, between two frames of an animation.
+	fn sleep(&mut self, duration: Duration) {
+		std::thread::sleep(duration);
+	}
+}
+
+/// Which frontend to play with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+	/// Needs the ncurses library, and the program built with the ncurses feature.
+	Ncurses,
+	/// Plain escape sequences, for the terminals of the systems without ncurses.
+	Ansi,
+}
+
+impl Default for Kind {
+	fn default() -> Self {
+		if cfg!(feature = "ncurses") {Kind::Ncurses} else {Kind::Ansi}
+	}
+}
+
+pub fn parse_kind(s: &str) -> Result<Kind, String> {
+	match s {
+		"ncurses" if cfg!(feature = "ncurses") => Ok(Kind::Ncurses),
+		"ncurses" => Err(String::from("this build has no ncurses frontend, try ansi")),
+		"ansi" => Ok(Kind::Ansi),
+		_ => Err(format!("unknown frontend '{}', expected ncurses or ansi", s)),
+	}
+}
+
+/// Takes over the terminal with that frontend. It's given back when the frontend is dropped.
+pub fn open(kind: Kind) -> Result<Box<dyn Frontend>, &'static str> {
+	match kind {
+		#[cfg(feature = "ncurses")]
+		Kind::Ncurses => Ok(Box::new(curses::Curses::new()?)),
+		#[cfg(not(feature = "ncurses"))]
+		Kind::Ncurses => Err("This build has no ncurses frontend."),
+		Kind::Ansi => Ok(Box::new(ansi::Ansi::new()?)),
+	}
+}
+
+/// Puts the terminal back the way it was, whatever frontend had it. Used by the panic hook,
+/// so that the panic message doesn't land in the middle of the board.
+pub fn restore_terminal() {
+	#[cfg(feature = "ncurses")]
+	curses::free_ncurses();
+	ansi::restore();
+}
+
+/// A screen in memory, for the frontends that draw on a screen they don't keep themselves.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Buffer {
+	lines: usize,
+	cols: usize,
+	glyphs: Vec<Glyph>,
+}
+
+impl Buffer {
+	pub fn new((lines, cols): (usize, usize)) -> Buffer {
+		Buffer { lines, cols, glyphs: vec![Glyph::default(); lines * cols] }
+	}
+
+	pub fn size(&self) -> (usize, usize) {
+		(self.lines, self.cols)
+	}
+
+	pub fn erase(&mut self) {
+		self.glyphs.fill(Glyph::default());
+	}
+
+	pub fn put(&mut self, line: i32, col: i32, glyph: Glyph) {
+		if (0..self.lines as i32).contains(&line) && (0..self.cols as i32).contains(&col) {
+			self.glyphs[line as usize * self.cols + col as usize] = glyph;
+		}
+	}
+
+	/// The glyph at that place, which must be on the screen.
+	pub fn get(&self, line: usize, col: usize) -> Glyph {
+		self.glyphs[line * self.cols + col]
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn buffer() {
+		let mut b = Buffer::new((2, 3));
+		b.put(0, 1, Glyph::new('a', Style::colour(Colour::Red).bold()));
+		b.put(-1, 0, Glyph::new('b', Style::default()));
+		b.put(1, 3, Glyph::new('c', Style::default()));
+		assert_eq!(b.get(0, 1).c, 'a');
+		assert!(b.get(0, 1).style.bold);
+		assert_eq!(b.glyphs.iter().filter(|g| g.c != ' ').count(), 1);
+		b.erase();
+		assert_eq!(b, Buffer::new((2, 3)));
+
+		assert_eq!(parse_kind("ansi"), Ok(Kind::Ansi));
+		assert!(parse_kind("gtk").is_err());
+	}
+}
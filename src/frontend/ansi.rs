@@ -0,0 +1,355 @@
+/*
+ * The frontend that needs nothing but a terminal that understands the ANSI escape sequences,
+ * which they all do nowadays.
+ *
+ * The terminal is put in cbreak mode with termios, the screen is drawn in a buffer and only
+ * the characters that changed are sent to it.
+ */
+
+use super::{Buffer, Colour, Frontend, Glyph, Input, Style};
+use crate::keys::{Direction, Key};
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+// Switch to the alternate screen, hide the cursor and report the mouse buttons, in SGR format.
+const ENTER: &str = "\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h";
+const LEAVE: &str = "\x1b[?1006l\x1b[?1000l\x1b[?25h\x1b[0m\x1b[?1049l";
+// How long to wait for the rest of an escape sequence, before taking the escape for a key.
+const ESCAPE_DELAY: Duration = Duration::from_millis(25);
+
+// The settings of the terminal before the game took it over, to put them back. Written once,
+// before the signal handlers are installed, then only read: a signal handler can't take a lock.
+static SAVED: OnceLock<libc::termios> = OnceLock::new();
+// Whether the frontend holds the terminal.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+// Set by SIGWINCH.
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+/// Holds the terminal, in cbreak mode and on the alternate screen, until it's dropped.
+pub struct Ansi {
+	/// What is drawn, and what the terminal shows. None until the first refresh.
+	back: Buffer,
+	front: Option<Buffer>,
+	/// The bytes read and not turned into inputs yet.
+	pending: Vec<u8>,
+}
+
+impl Ansi {
+	pub fn new() -> Result<Ansi, &'static str> {
+		if unsafe { libc::isatty(0) == 0 || libc::isatty(1) == 0 } {
+			return Err("The ANSI frontend needs a terminal.");
+		}
+		if TAKEN.swap(true, Ordering::SeqCst) {
+			return Err("The terminal is already taken.");
+		}
+		// Like the cbreak mode of ncurses: the keys come as they are typed, without an echo,
+		// and ctrl+C still interrupts the game.
+		let mut t: libc::termios = unsafe { std::mem::zeroed() };
+		if unsafe { libc::tcgetattr(0, &mut t) } != 0 {
+			TAKEN.store(false, Ordering::SeqCst);
+			return Err("Couldn't read the settings of the terminal.");
+		}
+		// a second frontend finds the terminal as the first one left it, keep the first settings.
+		SAVED.get_or_init(|| t);
+		t.c_lflag &= !(libc::ICANON | libc::ECHO);
+		t.c_iflag &= !(libc::ICRNL | libc::IXON);
+		t.c_cc[libc::VMIN] = 1;
+		t.c_cc[libc::VTIME] = 0;
+		if unsafe { libc::tcsetattr(0, libc::TCSAFLUSH, &t) } != 0 {
+			TAKEN.store(false, Ordering::SeqCst);
+			return Err("Couldn't set up the terminal.");
+		}
+
+		unsafe {
+			libc::signal(libc::SIGWINCH, on_resize as extern "C" fn(libc::c_int) as libc::sighandler_t);
+			libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+			libc::signal(libc::SIGTERM, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+		}
+		write_all(ENTER);
+		return Ok(Ansi { back: Buffer::new(terminal_size()), front: None, pending: Vec::new() });
+	}
+
+	/// Wait for more bytes, until the timeout. Returns false if none came.
+	fn read(&mut self, timeout: Option<Duration>) -> bool {
+		let mut fd = libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 };
+		let ms = timeout.map_or(-1, |d| d.as_millis().try_into().unwrap_or(i32::MAX));
+		if unsafe { libc::poll(&mut fd, 1, ms) } <= 0 {
+			return false; // the time ran out, or a signal came
+		}
+		let mut bytes = [0u8; 256];
+		let n = unsafe { libc::read(0, bytes.as_mut_ptr().cast(), bytes.len()) };
+		if n <= 0 {
+			return false;
+		}
+		self.pending.extend_from_slice(&bytes[..n as usize]);
+		return true;
+	}
+}
+
+impl Frontend for Ansi {
+	fn size(&self) -> (usize, usize) {
+		self.back.size()
+	}
+
+	fn erase(&mut self) {
+		self.back.erase();
+	}
+
+	fn put(&mut self, line: i32, col: i32, glyph: Glyph) {
+		self.back.put(line, col, glyph);
+	}
+
+	fn refresh(&mut self) {
+		let (lines, cols) = self.back.size();
+		let mut out = String::new();
+		// Where the cursor of the terminal is, and the style it writes in.
+		let mut at = None;
+		let mut style = None;
+		for line in 0..lines {
+			for col in 0..cols {
+				let glyph = self.back.get(line, col);
+				if self.front.as_ref().is_some_and(|f| f.get(line, col) == glyph) {
+					continue;
+				}
+				if at != Some((line, col)) {
+					let _ = write!(out, "\x1b[{};{}H", line + 1, col + 1);
+				}
+				if style != Some(glyph.style) {
+					out.push_str(&sgr(glyph.style));
+					style = Some(glyph.style);
+				}
+				out.push(glyph.c);
+				at = Some((line, col + 1));
+			}
+		}
+		if self.front.is_none() {
+			out.insert_str(0, "\x1b[0m\x1b[2J");
+		}
+		write_all(&out);
+		self.front = Some(self.back.clone());
+	}
+
+	fn input(&mut self, timeout: Option<Duration>) -> Option<Input> {
+		let start = Instant::now();
+		loop {
+			if RESIZED.swap(false, Ordering::SeqCst) {
+				self.back = Buffer::new(terminal_size());
+				self.front = None;
+				return Some(Input::Resize);
+			}
+			match parse(&self.pending) {
+				Parsed::Input(input, n) => {
+					self.pending.drain(..n);
+					return Some(input);
+				}
+				// The escape may be a key of its own, or the start of a sequence.
+				Parsed::Incomplete => {
+					if !self.read(Some(ESCAPE_DELAY)) {
+						let input = if self.pending == [0x1B] {Input::Key(Key::Escape)} else {Input::Unknown};
+						self.pending.clear();
+						return Some(input);
+					}
+				}
+				Parsed::Nothing => {
+					let left = timeout.map(|t| t.saturating_sub(start.elapsed()));
+					if left.is_some_and(|l| l.is_zero()) {
+						return None;
+					}
+					// bytes came, or a signal, or the time ran out: look again.
+					self.read(left);
+				}
+			}
+		}
+	}
+
+	fn flush_input(&mut self) {
+		unsafe { libc::tcflush(0, libc::TCIFLUSH) };
+		self.pending.clear();
+	}
+}
+
+impl Drop for Ansi {
+	fn drop(&mut self) {
+		restore();
+		unsafe {
+			libc::signal(libc::SIGWINCH, libc::SIG_DFL);
+			libc::signal(libc::SIGINT, libc::SIG_DFL);
+			libc::signal(libc::SIGTERM, libc::SIG_DFL);
+		}
+	}
+}
+
+/// Put the terminal back the way it was, if the frontend had it. Does nothing otherwise.
+pub fn restore() {
+	if !TAKEN.swap(false, Ordering::SeqCst) {
+		return;
+	}
+	if let Some(t) = SAVED.get() {
+		write_all(LEAVE);
+		unsafe { libc::tcsetattr(0, libc::TCSAFLUSH, t) };
+	}
+}
+
+extern "C" fn on_resize(_: libc::c_int) {
+	RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// Ctrl+C ends the game at once, like it does with ncurses. The terminal is put back first,
+/// with nothing but what a signal handler is allowed to call: atomics, and the write, tcsetattr
+/// and _exit system calls.
+extern "C" fn on_interrupt(signal: libc::c_int) {
+	if TAKEN.load(Ordering::SeqCst) {
+		if let Some(t) = SAVED.get() {
+			unsafe {
+				libc::write(1, LEAVE.as_ptr().cast(), LEAVE.len());
+				libc::tcsetattr(0, libc::TCSAFLUSH, t);
+			}
+		}
+	}
+	unsafe { libc::_exit(128 + signal) };
+}
+
+fn write_all(s: &str) {
+	let mut out = std::io::stdout().lock();
+	let _ = out.write_all(s.as_bytes());
+	let _ = out.flush();
+}
+
+/// The lines and columns of the terminal.
+fn terminal_size() -> (usize, usize) {
+	let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+	if unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) } != 0 || size.ws_row == 0 {
+		return (24, 80);
+	}
+	(size.ws_row.into(), size.ws_col.into())
+}
+
+/// The escape sequence that writes in that style.
+fn sgr(style: Style) -> String {
+	let mut s = String::from("\x1b[0");
+	if style.bold {
+		s.push_str(";1");
+	}
+	if style.dim {
+		s.push_str(";2");
+	}
+	if style.reverse {
+		s.push_str(";7");
+	}
+	if let Some(colour) = style.colour {
+		let n = match colour {
+			Colour::Red => 1,
+			Colour::Green => 2,
+			Colour::Yellow => 3,
+			Colour::Blue => 4,
+			Colour::Magenta => 5,
+			Colour::Cyan => 6,
+			Colour::White => 7,
+		};
+		let _ = write!(s, ";3{};40", n);
+	}
+	s.push('m');
+	return s;
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Parsed {
+	/// An input, and how many bytes it took.
+	Input(Input, usize),
+	/// The start of an escape sequence.
+	Incomplete,
+	Nothing,
+}
+
+/// The first input of the bytes read from the terminal.
+fn parse(bytes: &[u8]) -> Parsed {
+	let key = |k, n| Parsed::Input(Input::Key(k), n);
+	match bytes {
+		[] => Parsed::Nothing,
+		[0x1B] | [0x1B, b'[' | b'O'] => Parsed::Incomplete,
+		// an escape followed by anything but a sequence is a key of its own.
+		[0x1B, b'[' | b'O', rest @ ..] => {
+			// the parameters, then the final byte.
+			let Some(end) = rest.iter().position(|b| (0x40..=0x7E).contains(b)) else {
+				return if rest.len() < 32 {Parsed::Incomplete} else {Parsed::Input(Input::Unknown, bytes.len())};
+			};
+			let params = std::str::from_utf8(&rest[..end]).unwrap_or("");
+			let input = sequence(params, rest[end]);
+			Parsed::Input(input, end + 3)
+		}
+		[0x1B, ..] => key(Key::Escape, 1),
+		[b'\r' | b'\n', ..] => key(Key::Enter, 1),
+		[c @ 0x20..=0x7E, ..] => key(Key::Char(char::from(*c)), 1),
+		// a character that doesn't fit in a byte, skip all of it.
+		[c @ 0xC0..=0xFF, rest @ ..] => {
+			let n = 1 + rest.iter().take(c.leading_ones() as usize - 1).take_while(|&&b| b & 0xC0 == 0x80).count();
+			Parsed::Input(Input::Unknown, n)
+		}
+		[_, ..] => Parsed::Input(Input::Unknown, 1),
+	}
+}
+
+/// The input behind a control sequence, from its parameters and its final byte.
+fn sequence(params: &str, last: u8) -> Input {
+	let direction = match last {
+		b'A' => Some(Direction::Up),
+		b'B' => Some(Direction::Down),
+		b'C' => Some(Direction::Right),
+		b'D' => Some(Direction::Left),
+		_ => None,
+	};
+	match (params, last, direction) {
+		("" | "1", _, Some(d)) => Input::Key(Key::Arrow(d)),
+		("1;2", _, Some(d)) => Input::Key(Key::ShiftArrow(d)),
+		("5", b'~', _) => Input::PageUp,
+		("6", b'~', _) => Input::PageDown,
+		// the mouse, in SGR format: <button;column;line, M when pressed and m when released.
+		(p, b'M' | b'm', _) if p.starts_with('<') => {
+			let n: Vec<i32> = p[1..].split(';').filter_map(|n| n.parse().ok()).collect();
+			match n[..] {
+				[0, col, line] => Input::Mouse { line: line - 1, col: col - 1, pressed: last == b'M' },
+				_ => Input::Unknown,
+			}
+		}
+		_ => Input::Unknown,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn keys() {
+		let input = |b: &[u8]| match parse(b) {
+			Parsed::Input(input, n) => {
+				assert_eq!(n, b.len(), "{:?}", b);
+				input
+			}
+			p => panic!("{:?} gave {:?}", b, p),
+		};
+		assert_eq!(input(b"z"), Input::Key(Key::Char('z')));
+		assert_eq!(input(b"\r"), Input::Key(Key::Enter));
+		assert_eq!(input(b"\x1b[A"), Input::Key(Key::Arrow(Direction::Up)));
+		assert_eq!(input(b"\x1bOD"), Input::Key(Key::Arrow(Direction::Left)));
+		assert_eq!(input(b"\x1b[1;2B"), Input::Key(Key::ShiftArrow(Direction::Down)));
+		assert_eq!(input(b"\x1b[5~"), Input::PageUp);
+		assert_eq!(input(b"\x1b[<0;12;3M"), Input::Mouse { line: 2, col: 11, pressed: true });
+		assert_eq!(input(b"\x1b[<0;12;3m"), Input::Mouse { line: 2, col: 11, pressed: false });
+		assert_eq!(input("é".as_bytes()), Input::Unknown);
+		assert_eq!(parse(b"\x1b"), Parsed::Incomplete);
+		assert_eq!(parse(b"\x1b[<0;1"), Parsed::Incomplete);
+		assert_eq!(parse(b"\x1bz"), Parsed::Input(Input::Key(Key::Escape), 1));
+		assert_eq!(parse(b"ab"), Parsed::Input(Input::Key(Key::Char('a')), 1));
+	}
+
+	#[test]
+	fn styles() {
+		assert_eq!(sgr(Style::default()), "\x1b[0m");
+		assert_eq!(sgr(Style::colour(Colour::Blue).bold().reverse()), "\x1b[0;1;7;34;40m");
+	}
+}
@@ -0,0 +1,352 @@
+/*
+ * The ncurses frontend.
+ */
+
+use super::{Colour, Frontend, Glyph, Input, Style};
+use crate::keys::{Direction, Key};
+
+use ncurses::*;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static NCURSES_FLAG: AtomicBool = AtomicBool::new(false); // true if NCurses is active
+static NCURSES_LOCK: AtomicBool = AtomicBool::new(false); // lock for NCurses' critical section
+
+/// Holds ncurses, and with it the terminal. There can be only one at a time.
+///
+/// NCurses is unsafe and the terminal it controls is a global state. So any function that deals with
+/// ncurses requires a &mut Curses so that Rust's borrow checker will prevent a concurrent
+/// call on any function that deals with ncurses.
+pub struct Curses {
+	input_timeout: i32, // the timeout given to ncurses, in milliseconds, negative means blocking.
+}
+
+impl Curses {
+	pub fn new() -> Result<Curses, &'static str> {
+
+		// Spin on the lock to handle NCurses, as long as "true" was stored,
+		while NCURSES_LOCK.swap(true, Ordering::SeqCst) {}
+		// Test and set
+		let f = NCURSES_FLAG.swap(true, Ordering::SeqCst);
+		if f {
+			NCURSES_LOCK.store(false, Ordering::SeqCst);
+			return Err("NCurses was already initialized by another Tui.");
+		}
+
+		let r = init_ncurses();
+		NCURSES_LOCK.store(false, Ordering::SeqCst); // end of NCurses' critical section
+		if r.is_err() {
+			NCURSES_FLAG.store(false, Ordering::SeqCst);
+			return Err("NCurses could not be initialized correctly.");
+		}
+		return Ok(Curses { input_timeout: -1 });
+	}
+
+	fn set_timeout(&mut self, delay: Option<Duration>) {
+		let ms = match delay {
+			Some(d) => d.as_millis().try_into().unwrap_or(i32::MAX),
+			None => -1,
+		};
+		if ms != self.input_timeout {
+			self.input_timeout = ms;
+			timeout(ms);
+		}
+	}
+}
+
+impl Frontend for Curses {
+	fn size(&self) -> (usize, usize) {
+		(LINES().max(0) as usize, COLS().max(0) as usize)
+	}
+
+	fn erase(&mut self) {
+		erase();
+	}
+
+	fn put(&mut self, line: i32, col: i32, glyph: Glyph) {
+		let attr = attributes(glyph.style);
+		match acs(glyph.c) {
+			Some(c) => {
+				mvaddch(line, col, c | attr);
+			}
+			None if glyph.c.is_ascii() => {
+				mvaddch(line, col, glyph.c as chtype | attr);
+			}
+			None => {
+				attron(attr);
+				mvaddstr(line, col, glyph.c.encode_utf8(&mut [0; 4]));
+				attroff(attr);
+			}
+		}
+	}
+
+	fn put_str(&mut self, line: i32, col: i32, text: &str, style: Style) {
+		// ncurses would carry on with the next line, cut the text at the edge instead.
+		let room = (COLS() - col).max(0) as usize;
+		let text: String = text.chars().take(room).collect();
+		let attr = attributes(style);
+		attron(attr);
+		mvaddstr(line, col, &text);
+		attroff(attr);
+	}
+
+	fn refresh(&mut self) {
+		refresh();
+	}
+
+	fn input(&mut self, timeout: Option<Duration>) -> Option<Input> {
+		self.set_timeout(timeout);
+		// TODO if it's a weird char (eg å) then it won't fetch all the bytes.
+		// look in the commented main() dead code below to see how to suck up all the bytes.
+		let c = getch();
+		let input = match c {
+			ERR => return None,
+			KEY_MOUSE => {
+				let mut event = MEVENT { id: 0, x: 0, y: 0, z: 0, bstate: 0 };
+				if getmouse(&mut event) != OK {
+					Input::Unknown
+				} else if event.bstate & BUTTON1_PRESSED as mmask_t != 0 {
+					Input::Mouse { line: event.y, col: event.x, pressed: true }
+				} else if event.bstate & BUTTON1_RELEASED as mmask_t != 0 {
+					Input::Mouse { line: event.y, col: event.x, pressed: false }
+				} else {
+					Input::Unknown
+				}
+			}
+			KEY_RESIZE => Input::Resize,
+			KEY_PPAGE => Input::PageUp,
+			KEY_NPAGE => Input::PageDown,
+			// The rest of a character that doesn't fit in a byte.
+			0x80..=0xFF => {
+				empty_stdin(self);
+				Input::Unknown
+			}
+			_ => to_key(c).map_or(Input::Unknown, Input::Key),
+		};
+		return Some(input);
+	}
+
+	fn flush_input(&mut self) {
+		flushinp();
+	}
+
+	fn sleep(&mut self, duration: Duration) {
+		napms(duration.as_millis().try_into().unwrap_or(i32::MAX));
+	}
+}
+
+impl Drop for Curses {
+	fn drop(&mut self) {
+		free_ncurses();
+	}
+}
+
+/// Suck up all the bytes in the buffer of the standard input throught NCurses's getch()
+/// and return control once the buffer is empty. Panic if it deems the buffer was too full.
+fn empty_stdin(ncurses_guard: &mut Curses) {
+	// Non-blocking mode.
+	if nodelay(stdscr(), true) == ERR {
+		endwin();
+		panic!("Couldn't set no-delay mode for stdin.");
+	}
+	for _ in 0..64 {
+		if getch() == ERR {
+			// Ok, the input buffer is empty, we can exit the function.
+			// Back to blocking mode, or whatever delay the frontend had.
+			if nodelay(stdscr(), false) == ERR {
+				endwin();
+				panic!("Couldn't set yes-delay mode for stdin.");
+			}
+			timeout(ncurses_guard.input_timeout);
+			return;
+		}
+	}
+	// If this part is reached, it means we should crash because the buffer was too full.
+	// it is a conservative security feature. The user input is not trusted.
+	panic!("There are too many characters in the stdin buffer yo. Aborting. Wtf were u doing.");
+}
+
+// pub fn main() -> Result<i32, ()> {
+// 	init_ncurses()?;
+// 	addstr("Press 'q' to quit\n");
+// 	loop {
+// 		// Set blocking
+// 		if nodelay(stdscr(), false) == ERR {
+// 			endwin();
+// 			return Err(());
+// 		}
+// 		let mut c = getch();
+// 		if c == 'q' as i32 || c == ERR {
+// 			break;
+// 		}
+// 		// Set non-blocking
+// 		if nodelay(stdscr(), true) == ERR {
+// 			endwin();
+// 			return Err(());
+// 		}
+// //TODO add a compter to crash if the user provided 36 characters in one click. But it's buffered
+// 		let mut vec = vec![0u8; 4];
+// 		'tag: for i in 1..4 {
+// 			let second = getch();
+// 			if second == ERR {break 'tag;}
+//
+// 			let byte: u8 = second.try_into().unwrap_or_else(|_| {
+// 						endwin(); panic!("that char was bigger than a c char")});
+//
+// 			// unsafe {
+// 			// 	let pointer = (&mut c as *mut i32).cast::<u8>();
+// 			// 	pointer.add(i).write(byte);
+// 			// }
+// 			vec[i] = byte;
+// 		}
+//
+// 		if 0 < c && c < 255 {
+// 			vec[0] = (c & 0x000000FF) as u8;
+// 		}
+// 		let s = String::from_utf8(vec).unwrap_or_else(|e|{
+// 			let mut s = String::new();
+// 			write!(&mut s, "{}", e).unwrap();
+// 			return s;
+// 		});
+// 		let ch = s.chars().next();
+// 		let mut string = String::new();
+// 		writeln!(&mut string, "{} (unicode: {:?})", c, ch).unwrap();
+// 		addstr(&string[..]);
+// 	}
+//
+//
+// 	endwin();
+// 	return Ok('F' as i32);
+// }
+
+
+// fn sub_window() -> Result<WINDOW, ()> {
+// 	let w = subwin(stdscr(), 10, 10, 10, 10);
+// 	if w.is_null() {return Err(());}
+//
+// 	mvwaddch(w, 0,0, 'B' as chtype | A_BOLD() | A_UNDERLINE() | COLOR_PAIR(3) | A_REVERSE());
+// 	touchwin(stdscr());
+// 	wrefresh(w);
+// 	Ok(w)
+// }
+
+/// Uninitialize NCurses. Thread-safe.
+/// Call before the standard panic to ensure NCurses is freed. Otherwise the panic message may land
+/// on NCurses' screen.
+pub fn free_ncurses() {
+	// spin-lock
+	while NCURSES_LOCK.swap(true, Ordering::SeqCst) {}
+	if NCURSES_FLAG.swap(false, Ordering::SeqCst) {
+		endwin(); // The flag was "true" so NCurses is active and needs to be uninitialized.
+		// NCURSES_FLAG is now false, whatever state it was in before.
+	}
+	// release the spin-lock
+	NCURSES_LOCK.store(false, Ordering::SeqCst);
+}
+
+fn init_ncurses() -> Result<(), ()> {
+	// Use the locale of the user, or NCurses garbles the non-ascii characters of the status bar.
+	setlocale(LcCategory::all, "");
+	let r = initscr(); // Initializes stuff and put the terminal in that screen mode.
+	if r.is_null() {
+		eprintln!("Failed to initialize the terminal interface. Aborting.");
+		return Err(());
+	}
+	let mut r = false;
+	r |= ERR == cbreak(); // No char buffering. NCurses sucks typed chars immediately.
+	r |= ERR == noecho(); // Do not print the typed character on the terminal.
+	r |= ERR == keypad(stdscr(), true); // Allow capture of the arrow keys and others.
+	r |= ERR == nonl(); // Turn '\n' into '\r' when printed. "no newline". Not useful but anyway.
+	// r |= clearok(stdscr(), false); ???
+	curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE); // invisible cursor
+	// Report the mouse button going down and up apart, to tell the drags from the clicks.
+	mousemask((BUTTON1_PRESSED | BUTTON1_RELEASED) as mmask_t, None);
+	mouseinterval(0);
+	set_escdelay(25); // Escape is a key of its own, don't wait a second to be sure of it.
+	if r {
+		endwin();
+		eprintln!("sh!t happened: ncurses was not correctly initialized.");
+		return Err(());
+	}
+	if !has_colors() {
+		endwin();
+		eprintln!("No color! You can't play with no color, exiting.");
+		return Err(());
+	}
+	start_color();
+	wmove(stdscr(), 0, 0);
+
+	init_pair(1, COLOR_WHITE, COLOR_BLACK);
+	init_pair(2, COLOR_RED, COLOR_BLACK);
+	init_pair(3, COLOR_GREEN, COLOR_BLACK);
+	init_pair(4, COLOR_YELLOW, COLOR_BLACK);
+	init_pair(5, COLOR_BLUE, COLOR_BLACK);
+	init_pair(6, COLOR_MAGENTA, COLOR_BLACK);
+	init_pair(7, COLOR_CYAN, COLOR_BLACK);
+	init_pair(8, COLOR_BLACK, COLOR_WHITE);
+
+	erase(); // ensure the screen starts blank.
+
+	Ok(())
+}
+
+/// The ncurses attributes of a style.
+fn attributes(style: Style) -> chtype {
+	let mut attr = match style.colour {
+		Some(Colour::White) => COLOR_PAIR(1),
+		Some(Colour::Red) => COLOR_PAIR(2),
+		Some(Colour::Green) => COLOR_PAIR(3),
+		Some(Colour::Yellow) => COLOR_PAIR(4),
+		Some(Colour::Blue) => COLOR_PAIR(5),
+		Some(Colour::Magenta) => COLOR_PAIR(6),
+		Some(Colour::Cyan) => COLOR_PAIR(7),
+		None => A_NORMAL(),
+	};
+	if style.bold {
+		attr |= A_BOLD();
+	}
+	if style.dim {
+		attr |= A_DIM();
+	}
+	if style.reverse {
+		attr |= A_REVERSE();
+	}
+	return attr;
+}
+
+/// The line drawing character of ncurses for that character, if it has one.
+fn acs(c: char) -> Option<chtype> {
+	let c = match c {
+		super::HLINE => ACS_HLINE(),
+		super::VLINE => ACS_VLINE(),
+		super::CHECKER => ACS_CKBOARD(),
+		super::DIAMOND => ACS_DIAMOND(),
+		super::LANTERN => ACS_LANTERN(),
+		super::ULCORNER => ACS_ULCORNER(),
+		super::URCORNER => ACS_URCORNER(),
+		super::LLCORNER => ACS_LLCORNER(),
+		super::LRCORNER => ACS_LRCORNER(),
+		_ => return None,
+	};
+	return Some(c);
+}
+
+/// The key behind an ncurses key code, if the game knows about it.
+fn to_key(c: i32) -> Option<Key> {
+	let key = match c {
+		KEY_UP => Key::Arrow(Direction::Up),
+		KEY_LEFT => Key::Arrow(Direction::Left),
+		KEY_DOWN => Key::Arrow(Direction::Down),
+		KEY_RIGHT => Key::Arrow(Direction::Right),
+		KEY_SR => Key::ShiftArrow(Direction::Up),
+		KEY_SLEFT => Key::ShiftArrow(Direction::Left),
+		KEY_SF => Key::ShiftArrow(Direction::Down),
+		KEY_SRIGHT => Key::ShiftArrow(Direction::Right),
+		KEY_ENTER | 0x0A | 0x0D => Key::Enter,
+		0x1B => Key::Escape,
+		0x20..=0x7E => Key::Char(char::from(c as u8)),
+		_ => return None,
+	};
+	return Some(key);
+}
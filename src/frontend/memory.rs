@@ -0,0 +1,156 @@
+/*
+ * A frontend without a terminal, for the tests: the screen is kept in memory and the inputs
+ * are scripted in advance.
+ */
+
+use super::{Buffer, Colour, Frontend, Glyph, Input, Style};
+use crate::keys::Key;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A screen in memory. Its clones share the same screen, so that a test can keep one to look at
+/// what the Term it gave the other one drew.
+#[derive(Clone, Debug)]
+pub struct Memory {
+	state: Rc<RefCell<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+	/// What is drawn, and what was shown on the last refresh.
+	back: Buffer,
+	front: Buffer,
+	/// None is a wait for an input that times out.
+	inputs: VecDeque<Option<Input>>,
+	refreshes: usize,
+}
+
+impl Memory {
+	pub fn new(size: (usize, usize)) -> Memory {
+		let state = State {
+			back: Buffer::new(size),
+			front: Buffer::new(size),
+			inputs: VecDeque::new(),
+			refreshes: 0,
+		};
+		Memory { state: Rc::new(RefCell::new(state)) }
+	}
+
+	/// Queue an input, it comes after the ones already queued.
+	pub fn push(&self, input: Input) {
+		self.state.borrow_mut().inputs.push_back(Some(input));
+	}
+
+	/// Queue a wait that runs out of time before anything is typed.
+	pub fn time_out(&self) {
+		self.state.borrow_mut().inputs.push_back(None);
+	}
+
+	/// Queue the keys of some text, one character after the other.
+	pub fn type_text(&self, text: &str) {
+		for c in text.chars() {
+			self.push(Input::Key(Key::Char(c)));
+		}
+	}
+
+	/// How many times the screen was refreshed, the frames of the animations included.
+	pub fn refreshes(&self) -> usize {
+		self.state.borrow().refreshes
+	}
+
+	/// The characters shown on the screen, without the spaces at the end of the lines.
+	pub fn text(&self) -> String {
+		self.dump(|g| g.c)
+	}
+
+	/// The colours of the characters shown on the screen, one character per character of the
+	/// screen: the initial of the colour, in capital if the colour is reversed, `#` for the
+	/// reverse video without a colour, and a space for the default.
+	pub fn styles(&self) -> String {
+		self.dump(|g| style_char(g.style))
+	}
+
+	/// Which characters shown on the screen are bold, `!`, or dim, `~`, whatever their colour.
+	pub fn attributes(&self) -> String {
+		self.dump(|g| attribute_char(g.style))
+	}
+
+	/// The text, the colours and the attributes, as the golden files keep them.
+	pub fn snapshot(&self) -> String {
+		format!("{}\n----\n{}\n----\n{}\n", self.text(), self.styles(), self.attributes())
+	}
+
+	fn dump(&self, f: impl Fn(Glyph) -> char) -> String {
+		let state = self.state.borrow();
+		let (lines, cols) = state.front.size();
+		let mut out: Vec<String> = (0..lines).map(|line| {
+			let text: String = (0..cols).map(|col| f(state.front.get(line, col))).collect();
+			text.trim_end().to_string()
+		}).collect();
+		while out.last().is_some_and(|l| l.is_empty()) {
+			out.pop();
+		}
+		out.join("\n")
+	}
+}
+
+impl Frontend for Memory {
+	fn size(&self) -> (usize, usize) {
+		self.state.borrow().back.size()
+	}
+
+	fn erase(&mut self) {
+		self.state.borrow_mut().back.erase();
+	}
+
+	fn put(&mut self, line: i32, col: i32, glyph: Glyph) {
+		self.state.borrow_mut().back.put(line, col, glyph);
+	}
+
+	fn refresh(&mut self) {
+		let mut state = self.state.borrow_mut();
+		state.front = state.back.clone();
+		state.refreshes += 1;
+	}
+
+	/// The next scripted input. Once there are no more, it's as if the time ran out.
+	fn input(&mut self, _timeout: Option<Duration>) -> Option<Input> {
+		self.state.borrow_mut().inputs.pop_front().flatten()
+	}
+
+	fn flush_input(&mut self) {
+		self.state.borrow_mut().inputs.clear();
+	}
+
+	/// The animations play at once.
+	fn sleep(&mut self, _duration: Duration) {}
+}
+
+fn style_char(style: Style) -> char {
+	let initial = style.colour.map(|c| match c {
+		Colour::White => 'w',
+		Colour::Red => 'r',
+		Colour::Green => 'g',
+		Colour::Yellow => 'y',
+		Colour::Blue => 'b',
+		Colour::Magenta => 'm',
+		Colour::Cyan => 'c',
+	});
+	match (initial, style.reverse) {
+		(Some(c), true) => c.to_ascii_uppercase(),
+		(Some(c), false) => c,
+		(None, true) => '#',
+		(None, false) => ' ',
+	}
+}
+
+fn attribute_char(style: Style) -> char {
+	match (style.bold, style.dim) {
+		(true, _) => '!',
+		(false, true) => '~',
+		(false, false) => ' ',
+	}
+}
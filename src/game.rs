@@ -1,136 +1,426 @@
 /*
- * Handles the game logic.
+ * Runs a game: its configuration, and the loop that feeds the actions of the player to the
+ * engine and its events to the terminal.
  */
 
 
+use crate::ai;
+use crate::engine::{self, GameState, Mode, Rules};
+use crate::frontend;
+use crate::grid::Grid;
+use crate::highscores::{self, Entry, HighScores};
+use crate::keys::{self, Action, KeyMap, Preset};
+use crate::save::{self, Save};
+use crate::score::Score;
+use crate::term::{self, Term, Theme};
+
 use std::default::Default;
+use std::time::{Duration, Instant};
 
-static mut REMOVE_ME: [u8; 128] = [0; 128];
+/// Presets of board size and number of colours. The fewer the colours and the larger the
+/// board, the more moves there are to play.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+	Easy,
+	Normal,
+	Hard,
+}
 
-#[derive(Clone, Copy, Debug)]
+impl Difficulty {
+	/// Sets the board size and number of colours of the configuration.
+	pub fn apply(self, conf: &mut Config) {
+		let (height, width, colours) = match self {
+			Difficulty::Easy => (8, 8, 5),
+			Difficulty::Normal => (8, 6, 7),
+			Difficulty::Hard => (6, 5, 7),
+		};
+		conf.height = height;
+		conf.width = width;
+		conf.colours = colours;
+	}
+}
+
+#[derive(Clone, Debug)]
 pub struct Config {
 	pub width: u8,
 	pub height: u8,
-
+	/// How many different colours of gems are on the board, from grid::MIN_COLOURS to MAX_COLOURS.
+	pub colours: u8,
+	pub mode: Mode,
+	/// How long each step of a cascade is animated. A swap takes a quarter of it.
+	pub animation_delay: Duration,
+	pub theme: Theme,
+	/// What the game is drawn with.
+	pub frontend: frontend::Kind,
+	/// Seed of the random number generator of the grid. None picks one at random.
+	pub seed: Option<u64>,
+	/// Show a hint after the player has been idle that long. None disables it.
+	pub hint_delay: Option<Duration>,
+	/// Resume the game saved when the player last quit, if any.
+	pub resume: bool,
+	/// How many swaps the player has, in moves mode.
+	pub moves: u32,
+	/// The score to reach with them.
+	pub target: u64,
+	/// The key bindings, before the key file is applied.
+	pub keys: Preset,
+	/// The name of the player in the high scores.
+	pub name: String,
+	/// Print the high scores instead of playing.
+	pub show_scores: bool,
+	/// How far the solver looks ahead, when it plays.
+	pub ai: ai::Strategy,
+	/// Let the solver play, one move per animation delay, until a key is pressed.
+	pub autoplay: bool,
+	/// Have the solver play that many games of moves mode instead of playing, and print the scores.
+	pub benchmark: Option<u32>,
 }
 
 impl Default for Config {
 	fn default() -> Self {
+		let rules = Rules::default();
 		Config {
-			width: 7,
-			height: 7
+			width: rules.width,
+			height: rules.height,
+			colours: rules.colours,
+			mode: rules.mode,
+			animation_delay: Duration::from_secs(1),
+			theme: Theme::Classic,
+			frontend: frontend::Kind::default(),
+			seed: rules.seed,
+			hint_delay: Some(Duration::from_secs(10)),
+			resume: true,
+			moves: rules.moves,
+			target: rules.target,
+			keys: Preset::Azerty,
+			name: std::env::var("USER").unwrap_or_else(|_| String::from("player")),
+			show_scores: false,
+			ai: ai::Strategy::Greedy,
+			autoplay: false,
+			benchmark: None,
 		}
 	}
 }
 
-///
+impl Config {
+	/// The part of the configuration the engine plays by.
+	pub fn rules(&self) -> Rules {
+		return Rules {
+			width: self.width,
+			height: self.height,
+			colours: self.colours,
+			seed: self.seed,
+			mode: self.mode,
+			moves: self.moves,
+			target: self.target,
+		};
+	}
+}
+
+/// Run a game with the given configuration until the player quits.
 pub fn main(conf: &Config) -> Result<(), &str> {
 
-	let mut grid = crate::grid::Grid::new_rand(conf.height, conf.width);
-	let mut term = crate::term::Term::new()?;
+	if conf.show_scores {
+		print_scores();
+		return Ok(());
+	}
+	if let Some(games) = conf.benchmark {
+		return benchmark(conf, games);
+	}
+	// The engine draws nothing at random, the seed of a game without one is picked here.
+	let conf = &Config { seed: Some(conf.seed.unwrap_or_else(rand::random)), ..conf.clone() };
+	if conf.autoplay {
+		return autoplay(conf, &mut Term::new(conf.theme, frontend::open(conf.frontend)?));
+	}
 
-	// TODO refractor this. It's a ball of mud.
+	let mut state = GameState::new(&conf.rules())?;
+	// The table the game goes in. Levels change the board, but they all start on the same one.
+	let (lines, cols) = state.grid().size();
+	let board = (lines as u8, cols as u8);
+	let mut term = Term::new(conf.theme, frontend::open(conf.frontend)?);
 
-	// clean the grid of any match
-	for i in 0..64 {
-		if i == 63 {
-			return Err("Couldn't clean the grid of its matches.");
+	// pick up where the player left, if there's a saved game. Only endless games are saved.
+	let save_path = save::default_path();
+	let resume = conf.resume && conf.mode == Mode::Endless;
+	if let Some(path) = save_path.as_deref().filter(|p| resume && p.exists()) {
+		match Save::read(path) {
+			Ok(save) => {
+				state.resume(save.grid, save.score);
+				term.set_cursor(save.cursor);
+				term.messages.info("Welcome back!");
+			}
+			Err(e) => term.messages.error(e),
 		}
-		let (v, _) = grid.get_all_matches();
-		if v.is_empty() {
-			break;
+	}
+
+	// Wake up regularly to check whether the player needs a hint, and so that the Term can
+	// take the expired messages off the screen.
+	// It also keeps the clock ticking in blitz.
+	term.set_input_timeout(Some(Duration::from_millis(250)));
+
+	// the keys of the preset, and those of the key file on top of them.
+	term.keys = KeyMap::preset(conf.keys);
+	if let Some(path) = keys::default_path().filter(|p| p.exists()) {
+		match std::fs::read_to_string(&path) {
+			Ok(text) => {
+				if let Err(e) = term.keys.parse(&text) {
+					term.messages.error(e);
+				}
+			}
+			Err(_) => term.messages.error("Couldn't read the key file."),
 		}
-		grid.destroy_gems(&v);
 	}
+	term.help = term.keys.help(if conf.mode == Mode::Endless {"save and exit"} else {"give up"});
+	// When the engine was last told about the time.
+	let mut clock = Instant::now();
 
 	loop {
 
-		term.draw(&grid);
+		if let Some(ending) = state.over() {
+			let mut lines = vec![String::from(term::title(ending)), format!("Final score: {}", state.score().total())];
+			if let Some(target) = state.target() {
+				lines.push(format!("Target: {}", target));
+			}
+			lines.push(format!("Best combo: ×{}", state.score().best_combo()));
+			match record(conf, board, state.grid(), state.score()) {
+				Ok(table) => {
+					lines.push(String::new());
+					lines.extend(table);
+				}
+				Err(e) => term.messages.error(e),
+			}
+			lines.push(String::new());
+			lines.push(match term.keys.key(Action::Restart) {
+				Some(key) => format!("Press {} to play again, any other key to exit.", key),
+				None => String::from("Press any key to exit."),
+			});
+			term.set_status(&state.status());
+			let key = term.game_over(state.grid(), ending.outcome(), &lines);
+			if key.and_then(|k| term.keys.action(k)) != Some(Action::Restart) {
+				break;
+			}
+			// start over, on a fresh board.
+			for event in state.apply(engine::Action::Restart)? {
+				term.show(&state, &event, conf.animation_delay);
+			}
+			clock = Instant::now();
+		}
+
+		term.set_status(&state.status());
+		term.draw(state.grid());
 
-		// Call the process input routine until it returns something interesting for us.
-		let char = loop {
-			if let Some(c) = term.process_input(&grid) {
-				break c;
+		// Call the process input routine until it returns something interesting for us,
+		// or until the time is up.
+		let mut events;
+		let action = loop {
+			let now = Instant::now();
+			events = state.apply(engine::Action::Tick(now - clock))?;
+			clock = now;
+			if !events.is_empty() {
+				break None;
+			}
+			if let Some(a) = term.process_input(state.grid()) {
+				break Some(a);
+			}
+			if let Some(delay) = conf.hint_delay {
+				if term.hint.is_none() && term.idle_time() >= delay {
+					term.hint = ai::hint(state.grid());
+					term.draw(state.grid());
+				}
+			}
+			// the clock ticked.
+			if term.set_status(&state.status()) {
+				term.draw(state.grid());
 			}
 		};
 
-		// Convert the i32 into a char.
-		let char = {
-			if char & (!0x7F_i32) != 0 {
-				return Err("Non ascii characters are not supported.");
+		let play = match action {
+			// save and exit, or give up in the other modes
+			Some(Action::Quit) => break,
+			Some(Action::Hint) => Some(engine::Action::Hint),
+			Some(Action::Undo) => Some(engine::Action::Undo),
+			Some(Action::Redo) => Some(engine::Action::Redo),
+			// move two gems
+			Some(Action::Swap(d)) => {
+				let cursor = term.get_cursor();
+				d.neighbour(cursor, state.grid().size()).map(|n| engine::Action::Swap(cursor, n))
+			}
+			Some(Action::Log) => {
+				term.log_visible = !term.log_visible;
+				None
 			}
-			let u = char & 0x7F_i32;
-			unsafe {*(&u as *const i32).cast::<char>()}
+			Some(Action::Scores) => {
+				let lines = match conf.mode {
+					Mode::Endless => vec![String::from("Endless games aren't ranked, try blitz or moves.")],
+					_ => HighScores::read_default().map(|s| s.lines(conf.mode, board, None))
+					                                 .unwrap_or_else(|e| vec![String::from(e)]),
+				};
+				term.popup(state.grid(), &lines);
+				None
+			}
+			// the Term moves the cursor and picks up the gems on its own.
+			Some(Action::Cursor(_) | Action::Select | Action::Cancel) => None,
+			// only once the game is over.
+			Some(Action::Restart) => None,
+			// the clock had something to say.
+			None => None,
 		};
-
-		// exit
-		if char == 'w' {
-			break;
+		if let Some(play) = play {
+			events = state.apply(play)?;
 		}
-		// move two gems
-		else if ['z', 'q', 's', 'd'].contains(&char) {
-			let (x, y) = term.get_cursor();
-			let (x2, y2) = match char {
-				'z' if x != 0 => (x - 1, y),
-				'q' if y != 0 => (x, y - 1),
-				's' if x != grid.lines() - 1 => (x + 1, y),
-				'd' if y != grid.cols() - 1  => (x, y + 1),
-				_ => continue,
-			};
-
-			grid.permute((x, y), (x2, y2));
-			if grid.check_matches((x, y), (x2, y2)) {
-
-				// There, we can finally play.
-				let (gems, mut matches) = grid.get_all_matches();
-				// update the grid
-				grid.destroy_gems(&gems);
-				// there can be new matches formed.
-				for lvl in 0.. {
-					let (g, mut m) = grid.get_all_matches();
-					if g.is_empty() {
-						break;
-					}
-					term.draw(&grid);
-					grid.destroy_gems(&g);
-					matches.append(&mut m);
-					std::thread::sleep(std::time::Duration::from_secs(1));
-
-					// print the level. Plz rework on that later.
-					unsafe {
-						let s = format!("{}...", lvl);
-						let bytes = s.as_bytes();
-						let l = usize::min(bytes.len(), REMOVE_ME.len());
-						let dest = &mut REMOVE_ME[..l];
-						dest.copy_from_slice(bytes);
-						term.msg = std::str::from_utf8(dest).unwrap_or("Fatal error #D6tedD54EGD");
-					}
-				}
+		for event in &events {
+			term.show(&state, event, conf.animation_delay);
+		}
+	}
 
-				// and compute the score
-				let score: f32 = matches.into_iter()
-				                        .map(|(_gem, len)| {f32::from(len)*f32::from(len) / 9.})
-										.sum();
-				// print the score. Plz rework on that later.
-				unsafe {
-					let s = format!("Score of {}.", score);
-					let bytes = s.as_bytes();
-					let l = usize::min(bytes.len(), REMOVE_ME.len());
-					let dest = &mut REMOVE_ME[..l];
-					dest.copy_from_slice(bytes);
-					term.msg = std::str::from_utf8(dest).unwrap_or("Fatal error #D6tedD54EGD");
-				}
-			} else {
-				grid.permute((x, y), (x2, y2));
-				term.msg = "No match!";
+	let cursor = term.get_cursor();
+	drop(term); // always drop term before making use of stdout or stderr.
+
+	let score = state.score();
+	match conf.mode {
+		Mode::Endless => {
+			let save = Save { grid: state.grid().clone(), score: score.clone(), cursor };
+			match save_path.as_deref().ok_or("No place to save the game, HOME is not set.")
+			               .and_then(|path| save.write(path)) {
+				Ok(()) => println!("Game saved, see you soon."),
+				Err(e) => eprintln!("{} The game was not saved.", e),
 			}
+		}
+		Mode::Blitz | Mode::Moves => println!("Final score: {}", score.total()),
+		// levels never end on their own, the game is ranked when the player stops.
+		Mode::Levels => {
+			println!("Final score: {}", score.total());
+			match record(conf, board, state.grid(), score) {
+				Ok(table) => println!("\n{}\n", table.join("\n")),
+				Err(e) => eprintln!("{} The score was not recorded.", e),
+			}
+		}
+	}
+	println!("End.");
+	return Ok(());
+}
+
+/// Puts the game in its high score table, and returns the table, with the game marked if
+/// it made it there.
+fn record(conf: &Config, board: (u8, u8), grid: &Grid, score: &Score) -> Result<Vec<String>, &'static str> {
+	let path = highscores::default_path().ok_or("No place to keep the high scores, HOME is not set.")?;
+	let mut scores = HighScores::read(&path)?;
+	let entry = Entry::new(conf.mode, board, score.total(), score.best_combo(), grid.seed(), &conf.name);
+	let rank = scores.insert(entry);
+	if rank.is_some() {
+		scores.write(&path)?;
+	}
+	return Ok(scores.lines(conf.mode, board, rank));
+}
 
+/// Prints every high score table, for --scores.
+fn print_scores() {
+	let scores = match HighScores::read_default() {
+		Ok(scores) => scores,
+		Err(e) => return eprintln!("{}", e),
+	};
+	let tables = scores.tables();
+	if tables.is_empty() {
+		println!("No high score yet.");
+	}
+	for (i, (mode, size)) in tables.into_iter().enumerate() {
+		if i > 0 {
+			println!();
 		}
+		println!("{}", scores.lines(mode, size, None).join("\n"));
+	}
+}
 
+/// The game plays itself with the moves of the solver, until a key is pressed. Nothing is saved
+/// nor ranked, those scores aren't the player's. Once a game is over, another one starts.
+fn autoplay(conf: &Config, term: &mut Term) -> Result<(), &'static str> {
+	let mut state = GameState::new(&conf.rules())?;
+	let mut solver = ai::Solver::new(conf.ai, state.grid().seed());
+	term.messages.info("Press any key to stop.");
+	// the banners of the levels mustn't swallow that key.
+	term.keep_input = true;
+	let mut clock = Instant::now();
 
+	loop {
+		term.set_status(&state.status());
+		term.draw(state.grid());
+		if term.interrupted(state.grid(), conf.animation_delay) {
+			return Ok(());
+		}
+		let now = Instant::now();
+		let mut events = state.apply(engine::Action::Tick(now - clock))?;
+		clock = now;
+		if events.is_empty() {
+			// The engine reshuffles deadlocked boards, there's always a move.
+			let ((a, b), _) = solver.best_move(state.grid()).ok_or("No move left on the board.")?;
+			// move the cursor there first, as a player would.
+			term.set_cursor(a);
+			term.draw(state.grid());
+			events = state.apply(engine::Action::Swap(a, b))?;
+		}
+		for event in &events {
+			term.show(&state, event, conf.animation_delay);
+		}
+		if let Some(ending) = state.over() {
+			term.show_banner(state.grid(), &[String::from(term::title(ending)), format!("Final score: {}", state.score().total())]);
+			if term.interrupted(state.grid(), conf.animation_delay * 3) {
+				return Ok(());
+			}
+			for event in state.apply(engine::Action::Restart)? {
+				term.show(&state, &event, conf.animation_delay);
+			}
+			clock = Instant::now();
+		}
 	}
+}
 
-	drop(term); // always drop term before making use of stdout or stderr.
-	println!("End.");
+/// Print the scores of the games the solver played, and how they add up.
+fn benchmark(conf: &Config, games: u32) -> Result<(), &'static str> {
+	let scores = ai::benchmark(&conf.rules(), conf.ai, games)?;
+	let first = conf.seed.unwrap_or(0);
+	for (i, score) in scores.iter().enumerate() {
+		println!("Seed {}: {}", first.wrapping_add(i as u64), score);
+	}
+	if let (Some(best), Some(worst)) = (scores.iter().max(), scores.iter().min()) {
+		let average = scores.iter().sum::<u64>() as f64 / scores.len() as f64;
+		println!("Average: {:.0}, best: {}, worst: {}", average, best, worst);
+	}
 	return Ok(());
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::frontend::memory::Memory;
+	use crate::frontend::Input;
+	use crate::keys::Key;
+
+	#[test]
+	fn autoplay() {
+		// a few moves, then a key stops it.
+		let memory = Memory::new((30, 80));
+		let mut term = Term::new(Theme::Classic, Box::new(memory.clone()));
+		for _ in 0..5 {
+			memory.time_out();
+		}
+		memory.push(Input::Key(Key::Char('x')));
+		let conf = Config { seed: Some(5), autoplay: true, ..Default::default() };
+		super::autoplay(&conf, &mut term).unwrap();
+		let text = memory.text();
+		assert!(!text.contains("Score: 0 "), "{}", text);
+		assert!(text.contains("Seed: 5"));
+
+		// games over start again, and a click stops it too.
+		let memory = Memory::new((30, 80));
+		let mut term = Term::new(Theme::Classic, Box::new(memory.clone()));
+		// two moves, the banner, and the new game.
+		for _ in 0..3 {
+			memory.time_out();
+		}
+		memory.push(Input::Mouse { line: 0, col: 0, pressed: true });
+		let conf = Config { mode: Mode::Moves, moves: 2, ai: ai::Strategy::Random, ..conf };
+		super::autoplay(&conf, &mut term).unwrap();
+		assert!(memory.text().contains("Moves: 2 "), "{}", memory.text());
+		assert!(memory.text().contains("New game"), "{}", memory.text());
+	}
+}
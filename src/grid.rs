@@ -5,16 +5,28 @@
 // ndarray doc
 // https://docs.rs/ndarray/0.15.1/ndarray/
 use ndarray::Array2;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
 
+use std::collections::BTreeSet;
 use std::convert::TryInto;
 use std::default::Default;
 
 // The game is called match the three, after all.
 const NUMBER_TO_MATCH: u8 = 3;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// The fewest colours a grid can be played with. Below that, matches form on their own.
+pub const MIN_COLOURS: u8 = 4;
+/// All the colours of Gem.
+pub const MAX_COLOURS: u8 = 7;
+
+// How many times Grid::shuffle() tries to lay the gems out before giving up.
+const SHUFFLE_ATTEMPTS: u32 = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub enum Gem {
+	#[default]
 	Green,
 	Red,
 	Yellow,
@@ -24,12 +36,8 @@ pub enum Gem {
 	Cyan
 }
 
-impl Default for Gem {
-	fn default() -> Gem {Gem::Green}
-}
-
 impl Gem {
-	fn from_u8(x: u8) -> Gem {
+	pub fn from_u8(x: u8) -> Gem {
 		// x modulo 7 and then the matching.
 		match x % 7 {
 			0 => Gem::Green,
@@ -42,6 +50,120 @@ impl Gem {
 			_ => unreachable!()
 		}
 	}
+
+	/// Draws a gem among the first `colours` colours.
+	fn random<R: Rng>(rng: &mut R, colours: u8) -> Gem {
+		Gem::from_u8(rng.gen_range(0..colours))
+	}
+}
+
+/// What a gem does when it is destroyed, on top of disappearing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Special {
+	/// A plain gem.
+	#[default]
+	None,
+	/// A gem born from a match of four. It clears its whole line, or its whole column.
+	Striped(Stripes),
+	/// A gem born from two crossing matches, in a L or T shape. It clears the 3×3 square around it.
+	Bomb,
+	/// A gem born from a match of five. It matches with nothing, but can be swapped with any gem
+	/// and then clears every gem of that colour. If it is destroyed otherwise, it clears the
+	/// colour of the gem it was born from.
+	Hypercube,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stripes {
+	/// Clears the line of the gem.
+	Horizontal,
+	/// Clears the column of the gem.
+	Vertical,
+}
+
+/// The content of one tile of the grid: a gem and its special power, if any.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Cell {
+	pub gem: Gem,
+	pub special: Special,
+}
+
+impl From<Gem> for Cell {
+	fn from(gem: Gem) -> Cell {
+		Cell { gem, special: Special::None }
+	}
+}
+
+impl Cell {
+	/// The colour this cell matches with. A hypercube doesn't have one.
+	#[inline]
+	pub fn colour(&self) -> Option<Gem> {
+		if self.special == Special::Hypercube {
+			None
+		} else {
+			Some(self.gem)
+		}
+	}
+}
+
+/// A straight line of at least NUMBER_TO_MATCH gems of the same colour.
+#[derive(Clone, Debug)]
+struct Run {
+	gem: Gem,
+	points: Vec<(u8, u8)>,
+	horizontal: bool,
+}
+
+/// A connex group of matched gems of the same colour: a bar, or several crossing bars.
+#[derive(Clone, Debug)]
+struct Cluster {
+	runs: Vec<Run>,
+	points: BTreeSet<(u8, u8)>,
+	/// The points where a horizontal and a vertical bar cross.
+	crossings: Vec<(u8, u8)>,
+}
+
+/// What happened during one step of the resolution of a turn, see Grid::crush().
+#[derive(Clone, Debug, Default)]
+pub struct Crush {
+	/// All the matches (gem, length), horizontal and vertical.
+	pub matches: Vec<(Gem, u8)>,
+	/// Every destroyed point, be it matched or caught in the blast of a special gem.
+	pub destroyed: Vec<(u8, u8)>,
+	/// The special gems that went off.
+	pub detonated: Vec<Special>,
+	/// The special gems that were created, and where.
+	pub created: Vec<((u8, u8), Special)>,
+	/// How the gems fell in the holes, see Grid::gravity().
+	pub fall: Fall,
+}
+
+/// How the gems moved when the destroyed ones were taken off the board, see Grid::gravity().
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Fall {
+	/// The gems that fell, from a point (x, y) to another one lower in the same column.
+	pub moves: Vec<((u8, u8), (u8, u8))>,
+	/// The new gems, (column, line, gem). They come from above the board.
+	pub spawns: Vec<(u8, u8, Gem)>,
+}
+
+impl Fall {
+	/// How many lines each gem of a board of that size fell. The new gems fell from above the
+	/// board, as far as the number of gems spawned in their column.
+	pub fn drops(&self, (lines, cols): (usize, usize)) -> Array2<u8> {
+		let mut drops = Array2::<u8>::zeros((lines, cols));
+		for &((x, _), (x2, y)) in &self.moves {
+			drops[[x2.into(), y.into()]] = x2 - x;
+		}
+		let mut spawned = vec![0u8; cols];
+		for &(y, _, _) in &self.spawns {
+			spawned[usize::from(y)] += 1;
+		}
+		for &(y, x, _) in &self.spawns {
+			drops[[x.into(), y.into()]] = spawned[usize::from(y)];
+		}
+		return drops;
+	}
 }
 
 /*
@@ -54,58 +176,124 @@ impl Gem {
 ╚═══╧═══╧═══╝
 */
 
-// A single-nameless-element struct.
-pub struct Grid (pub Array2<Gem>);
+/// A swap of two neighbouring gems, ((x1, y1), (x2, y2)) in the matrix convention.
+pub type Move = ((usize, usize), (usize, usize));
+
+/// The gems, and the random number generator that draws the new ones.
+///
+/// The generator is seeded, so that a grid and all its refills can be replayed from the seed.
+#[derive(Clone)]
+pub struct Grid {
+	pub cells: Array2<Cell>,
+	/// New gems are drawn among that many colours.
+	colours: u8,
+	seed: u64,
+	rng: ChaCha8Rng,
+}
+
+impl From<Array2<Gem>> for Grid {
+	fn from(gems: Array2<Gem>) -> Grid {
+		Grid::from_cells(gems.mapv(Cell::from), MAX_COLOURS, 0)
+	}
+}
 
 impl std::fmt::Debug for Grid {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "Grid of gems:\n{:?}", self.0)
+		write!(f, "Grid of gems (seed {}):\n{:?}", self.seed, self.cells)
 	}
 }
 
 impl Grid {
 
-	pub fn new_from(lines: u8, cols: u8, gem: Gem) -> Grid {
+	/// Fails if the grid is smaller than 2×2, there would be no move to play.
+	pub fn new_from(lines: u8, cols: u8, gem: Gem) -> Result<Grid, &'static str> {
 		if lines < 2 || cols < 2 {
-			panic!("Grid's width and height shall not be smaller than 2.");
+			return Err("Grid's width and height shall not be smaller than 2.");
 		}
 
-		Grid(Array2::<Gem>::from_elem((lines.into(), cols.into()), gem))
+		Ok(Grid::from_cells(Array2::<Cell>::from_elem((lines.into(), cols.into()), Cell::from(gem)), MAX_COLOURS, 0))
 	}
 
-	pub fn new_rand(lines: u8, cols: u8) -> Grid {
+	/// A grid of random gems of `colours` different colours.
+	/// The same seed always gives the same grid, and the same refills.
+	pub fn new_rand(lines: u8, cols: u8, colours: u8, seed: u64) -> Result<Grid, &'static str> {
+		if !(MIN_COLOURS..=MAX_COLOURS).contains(&colours) {
+			return Err("The number of colours shall be between 4 and 7.");
+		}
+
+		let mut g = Grid::new_from(lines, cols, Gem::Green)?;
+		g.colours = colours;
+		g.reseed(seed);
 
-		let mut g = Grid::new_from(lines, cols, Gem::Green);
+		for cell in g.cells.iter_mut() {
+			*cell = Cell::from(Gem::random(&mut g.rng, colours));
+		}
+		return Ok(g);
+	}
 
-		let mut rng = rand::thread_rng();
-		for gem in g.0.iter_mut() {
-			*gem = Gem::from_u8(rng.gen());
+	/// Wraps the cells in a grid, with a generator fresh from the seed.
+	/// The new gems will be drawn among the first `colours` colours.
+	pub fn from_cells(cells: Array2<Cell>, colours: u8, seed: u64) -> Grid {
+		Grid {
+			cells,
+			colours,
+			seed,
+			rng: ChaCha8Rng::seed_from_u64(seed),
 		}
-		return g;
 	}
 
-	// pub fn size(&self) -> (usize, usize) {
-	// 	let s = self.0.shape();
-	// 	(s[0], s[1])
-	// } // remove if it's never needed.
+	/// Restarts the random number generator from the seed.
+	pub fn reseed(&mut self, seed: u64) {
+		self.seed = seed;
+		self.rng = ChaCha8Rng::seed_from_u64(seed);
+	}
+
+	/// How many colours the gems are drawn among.
+	pub fn colours(&self) -> u8 {
+		self.colours
+	}
+
+	/// The seed the grid was generated from.
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	/// How far the random number generator went since it was seeded.
+	/// Along with the seed, it is all that's needed to restore it with set_rng_position().
+	pub fn rng_position(&self) -> u128 {
+		self.rng.get_word_pos()
+	}
+
+	pub fn set_rng_position(&mut self, position: u128) {
+		self.rng.set_word_pos(position);
+	}
+
+	pub fn size(&self) -> (usize, usize) {
+		let s = self.cells.shape();
+		(s[0], s[1])
+	}
 	#[inline]
 	pub fn lines(&self) -> usize {
-		self.0.shape()[0]
+		self.cells.shape()[0]
 	}
 	#[inline]
 	pub fn cols(&self) -> usize {
-		self.0.shape()[1]
+		self.cells.shape()[1]
 	}
 	#[inline]
 	pub fn get(&self, x:usize, y:usize) -> Gem {
-		self.0[[x, y]]
+		self.cells[[x, y]].gem
+	}
+	#[inline]
+	pub fn cell(&self, x:usize, y:usize) -> Cell {
+		self.cells[[x, y]]
 	}
 
 	pub fn permute(&mut self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) {
-		let tmp = self.0[[x1, y1]];
-		self.0[[x1, y1]] = std::mem::replace(&mut self.0[[x2, y2]], tmp);
+		let tmp = self.cells[[x1, y1]];
+		self.cells[[x1, y1]] = std::mem::replace(&mut self.cells[[x2, y2]], tmp);
 	}
-	/** Returns (u0, u1, u2, u3) where
+	/* Returns (u0, u1, u2, u3) where
 
                      u1
 	                 |
@@ -121,24 +309,34 @@ impl Grid {
 	 * This function is useful to check if a player's move triggers matches or not.
 	 */
 	fn match_border_from_point(&self, root_x: usize, root_y: usize) -> (u8,u8,u8,u8) {
-		let gem = self.0[[root_x, root_y]];
+		let gem = match self.cell(root_x, root_y).colour() {
+			Some(gem) => gem,
+			// A hypercube doesn't match with anything, not even its neighbours.
+			None => return (root_y as u8, root_x as u8, root_y as u8, root_x as u8),
+		};
+		let same = |x, y| self.cell(x, y).colour() == Some(gem);
 
-		let o0 = (0..=root_y).rev()    .skip_while(|&y| self.0[[root_x, y]] == gem).next();
-		let o1 = (0..=root_x).rev()    .skip_while(|&x| self.0[[x, root_y]] == gem).next();
-		let o2 = (root_y..self.cols()) .skip_while(|&y| self.0[[root_x, y]] == gem).next();
-		let o3 = (root_x..self.lines()).skip_while(|&x| self.0[[x, root_y]] == gem).next();
+		let o0 = (0..=root_y).rev()    .find(|&y| !same(root_x, y));
+		let o1 = (0..=root_x).rev()    .find(|&x| !same(x, root_y));
+		let o2 = (root_y..self.cols()) .find(|&y| !same(root_x, y));
+		let o3 = (root_x..self.lines()).find(|&x| !same(x, root_y));
 
 		return (
-			o0.and_then(|y| Some(y + 1)).unwrap_or(0)             .try_into().unwrap(),
-			o1.and_then(|x| Some(x + 1)).unwrap_or(0)             .try_into().unwrap(),
-			o2.and_then(|y| Some(y - 1)).unwrap_or(self.cols()-1) .try_into().unwrap(),
-			o3.and_then(|x| Some(x - 1)).unwrap_or(self.lines()-1).try_into().unwrap(),
+			o0.map(|y| y + 1).unwrap_or(0)             .try_into().unwrap(),
+			o1.map(|x| x + 1).unwrap_or(0)             .try_into().unwrap(),
+			o2.map(|y| y - 1).unwrap_or(self.cols()-1) .try_into().unwrap(),
+			o3.map(|x| x - 1).unwrap_or(self.lines()-1).try_into().unwrap(),
 		);
 	}
 
 	/// Tests whether the two points are in any match or not.
 	/// After a move from the player, call this function to know if it a valid move.
+	/// A hypercube goes off whatever it is swapped with, so that counts as a match.
 	pub fn check_matches(&self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> bool {
+		if self.cells[[x1, y1]].special == Special::Hypercube || self.cells[[x2, y2]].special == Special::Hypercube {
+			return true;
+		}
+
 		let (y1_start, x1_start, y1_end, x1_end) = self.match_border_from_point(x1, y1);
 		let (y2_start, x2_start, y2_end, x2_end) = self.match_border_from_point(x2, y2);
 
@@ -155,42 +353,92 @@ impl Grid {
 	///        o o(o)o x
 	///        x x o x x
 	///
-	/// This function inserts in the provided BTreeSet all the new points in the matches.
-	// fn get_match_from_point(&self, x: usize, y: usize,
-	// 				collec: &mut BTreeSet<(u8, u8)>, matches: &mut Vec<(Gem, u8)>) {
-	//
-	// 	let (y_start, x_start, y_end, x_end) = self.match_border_from_point(x, y);
-	//
-	// 	if x_end - x_start + 1 >= NUMBER_TO_MATCH {
-	// 		// Check whether ALL the points in the new match already are in the BTreeSet.
-	// 		// However, they could be in but in other orthogonal matches...
-	// 		for i in x_start..=x_end {
-	// 			collec.insert((i as u8, y as u8));
-	// 		}
-	// 	}
-	//
-	// 	if y_end - y_start + 1 >= NUMBER_TO_MATCH {
-	// 		for j in y_start..=y_end {
-	// 			collec.insert((x as u8, j as u8));
-	// 		}
-	// 	}
-	// }
+	/// This function inserts in the provided BTreeSet all the points in the matches going
+	/// through the root point, and returns true if the root point is the center of a cross.
+	fn get_match_from_point(&self, x: usize, y: usize, collec: &mut BTreeSet<(u8, u8)>) -> bool {
+
+		let (y_start, x_start, y_end, x_end) = self.match_border_from_point(x, y);
+		let vertical = x_end - x_start + 1 >= NUMBER_TO_MATCH;
+		let horizontal = y_end - y_start + 1 >= NUMBER_TO_MATCH;
+
+		if vertical {
+			for i in x_start..=x_end {
+				collec.insert((i, y as u8));
+			}
+		}
+
+		if horizontal {
+			for j in y_start..=y_end {
+				collec.insert((x as u8, j));
+			}
+		}
+
+		return vertical && horizontal;
+	}
+
+	/// Groups all the matches of the grid into clusters of crossing bars.
+	fn get_clusters(&self) -> Vec<Cluster> {
+		let runs = self.get_runs();
+		let mut clusters: Vec<Cluster> = Vec::new();
+
+		for run in &runs {
+			if clusters.iter().any(|c| c.points.contains(&run.points[0])) {
+				continue; // already part of a cluster
+			}
+
+			// Spread from the first point of the run, bar after bar.
+			let mut points = BTreeSet::new();
+			let mut crossings = Vec::new();
+			let mut frontier = vec![run.points[0]];
+			points.insert(run.points[0]);
+			while let Some((x, y)) = frontier.pop() {
+				let mut bars = BTreeSet::new();
+				if self.get_match_from_point(x.into(), y.into(), &mut bars) {
+					crossings.push((x, y));
+				}
+				for p in bars {
+					if points.insert(p) {
+						frontier.push(p);
+					}
+				}
+			}
+
+			let runs = runs.iter().filter(|r| points.contains(&r.points[0])).cloned().collect();
+			clusters.push(Cluster { runs, points, crossings });
+		}
+		return clusters;
+	}
 
 	/// Probes the entire grid and returns two vectors:
 	/// - the first one contains all points (x, y) that are in a matches are should be destroyed.
-	///   (intended to feed destroy_gems())
+	///   (intended to feed gravity())
 	/// - the second one contains all matches (gem, length) horizontal and vertical.
+	///
 	/// No duplicate. The vectors will be empty if no match be.
+	#[allow(clippy::type_complexity)]
 	pub fn get_all_matches(&self) -> (Vec<(u8, u8)>, Vec<(Gem, u8)>) {
+		let runs = self.get_runs();
+
+		let matches = runs.iter().map(|r| (r.gem, r.points.len() as u8)).collect();
+		let mut points: Vec<(u8, u8)> = runs.into_iter().flat_map(|r| r.points).collect();
+
+		// Remove duplicate in points
+		points.sort();
+		points.dedup();
+
+		return (points, matches);
+	}
+
+	/// Probes the entire grid and returns all the matches, horizontal and vertical.
+	/// The points of two crossing matches show up in both.
+	fn get_runs(&self) -> Vec<Run> {
 
-		// Initialise the vector of matched points with capacity 3, the least possible.
-		let mut points: Vec<(u8, u8)> = Vec::with_capacity(3);
 		// Initialise the vector of matches with capacity 1, as most rounds will see only 1 match.
-		let mut matches: Vec<(Gem, u8)> = Vec::with_capacity(1);
+		let mut runs: Vec<Run> = Vec::with_capacity(1);
 
 		let mut accumulator = Vec::with_capacity(3);
 		let mut count = 1u8; // There cannot be over 255 matched gems in a 255×255 board.
-		let mut old_gem = Default::default();
+		let mut old_gem: Option<Gem> = None; // None is a hypercube, it matches with nothing.
 		// This variable is not given to the closure. It'll be sent to it as an argument.
 		// It allows to prevent a match crossing over two lines or two columns, but not only.
 		let mut match_is_continuous: bool;
@@ -203,20 +451,24 @@ impl Grid {
 		// so putting the logic in a closure avoids code duplication.
 		let mut closure = |x: u8,
  			               y: u8,
-						   gem: Gem,
-						   match_is_continuous: bool,| {
+						   gem: Option<Gem>,
+						   match_is_continuous: bool,
+						   horizontal: bool| {
 
-			if match_is_continuous && old_gem == gem {
+			if match_is_continuous && gem.is_some() && old_gem == gem {
 
 				accumulator.push((x, y));
 				count += 1;
 
 			} else {
 
-				if count >= NUMBER_TO_MATCH {
+				if let (true, Some(old_gem)) = (count >= NUMBER_TO_MATCH, old_gem) {
 					// It's a match!
-					matches.push((old_gem, count));
-					points.append(&mut accumulator);
+					runs.push(Run {
+						gem: old_gem,
+						points: accumulator.clone(),
+						horizontal,
+					});
 				}
 
 				accumulator.clear();
@@ -227,12 +479,12 @@ impl Grid {
 		};
 
 		// Horizontal
-		for (x, r) in self.0.rows().into_iter().enumerate() {
+		for (x, r) in self.cells.rows().into_iter().enumerate() {
 			// We enter in a new line, so there can't be a match with the previous tile.
 			match_is_continuous = false;
-			for (y, gem) in r.into_iter().enumerate() {
+			for (y, cell) in r.into_iter().enumerate() {
 
-				closure(x as u8, y as u8, *gem, match_is_continuous);
+				closure(x as u8, y as u8, cell.colour(), match_is_continuous, true);
 				match_is_continuous = true; // Next gems are (maybe) continuous.
 
 			}
@@ -241,44 +493,252 @@ impl Grid {
 		// Call the clusore on an unmatchable dummy value.
 		// In this call the closure will also reset count and accumulator, which is crucial.
 		match_is_continuous = false;
-		closure(0, 0, Gem::Red, match_is_continuous);
+		closure(0, 0, None, match_is_continuous, true);
 
 
 		// Vertical
-		for (y, c) in self.0.columns().into_iter().enumerate() {
+		for (y, c) in self.cells.columns().into_iter().enumerate() {
 			// We enter in a new column, so there shan't be a match with the previous tile.
 			match_is_continuous = false;
-			for (x, gem) in c.into_iter().enumerate() {
+			for (x, cell) in c.into_iter().enumerate() {
 
-				closure(x as u8, y as u8, *gem, match_is_continuous);
+				closure(x as u8, y as u8, cell.colour(), match_is_continuous, false);
 				match_is_continuous = true;
 
 			}
 		}
 		match_is_continuous = false;
-		closure(0, 0, Gem::Red, match_is_continuous);
+		closure(0, 0, None, match_is_continuous, false);
 
-		// Remove duplicate in points
-		points.sort();
-		points.dedup();
+		return runs;
+	}
 
-		return (points, matches);
+	/// Resolves one step of a turn: destroys all the matched gems, and lets the others fall down.
+	///
+	/// Each cluster of matches may leave a special gem behind, on whichever point of `swapped`
+	/// it contains, or else on its middle:
+	/// - a match of five or more makes a hypercube,
+	/// - crossing matches (L or T shapes) make a bomb, on their crossing,
+	/// - a match of four makes a striped gem.
+	///
+	/// A hypercube in `swapped` goes off and takes the colour of the other swapped gem with it.
+	/// Destroyed special gems go off, and may set off others.
+	///
+	/// Returns None if nothing was destroyed, in which case the turn is over.
+	pub fn crush(&mut self, swapped: &[(usize, usize)]) -> Option<Crush> {
+		let clusters = self.get_clusters();
+
+		let mut crush = Crush::default();
+		let mut hit = Array2::<bool>::from_elem(self.cells.raw_dim(), false);
+		let mut queue: Vec<(u8, u8)> = Vec::new();
+
+		// A hypercube swapped by the player takes the colour of its partner.
+		if let &[a, b] = swapped {
+			for &(h, other) in &[(a, b), (b, a)] {
+				if self.cells[h].special != Special::Hypercube {
+					continue;
+				}
+				let h8 = (h.0 as u8, h.1 as u8);
+				if self.cells[other].special == Special::Hypercube {
+					// Two hypercubes together, that's the whole board.
+					queue.extend(self.cells.indexed_iter().map(|((x, y), _)| (x as u8, y as u8)));
+				} else {
+					self.cells[h].gem = self.cells[other].gem;
+					queue.push(h8);
+				}
+			}
+		}
+
+		if clusters.is_empty() && queue.is_empty() {
+			return None;
+		}
+
+		let swapped_in = |c: &Cluster| {
+			c.points.iter().copied().find(|&(x, y)| swapped.contains(&(x.into(), y.into())))
+		};
+		for cluster in &clusters {
+			let mut longest = &cluster.runs[0];
+			for run in &cluster.runs {
+				crush.matches.push((run.gem, run.points.len() as u8));
+				if run.points.len() > longest.points.len() {
+					longest = run;
+				}
+			}
+			queue.extend(cluster.points.iter().copied());
+
+			let len = longest.points.len();
+			let middle = longest.points[(len - 1) / 2];
+			let created = if len >= 5 {
+				Some((swapped_in(cluster).unwrap_or(middle), Special::Hypercube))
+			} else if let Some(&crossing) = cluster.crossings.first() {
+				Some((swapped_in(cluster).unwrap_or(crossing), Special::Bomb))
+			} else if len == 4 {
+				let stripes = if longest.horizontal {Stripes::Horizontal} else {Stripes::Vertical};
+				Some((swapped_in(cluster).unwrap_or(middle), Special::Striped(stripes)))
+			} else {
+				None
+			};
+			crush.created.extend(created);
+		}
+
+		// Destroy everything in the queue, and let the special gems add their victims to it.
+		while let Some((x, y)) = queue.pop() {
+			let (x, y) = (usize::from(x), usize::from(y));
+			if std::mem::replace(&mut hit[[x, y]], true) {
+				continue; // already destroyed
+			}
+			let Cell { gem, special } = self.cells[[x, y]];
+			match special {
+				Special::None => {},
+				Special::Striped(Stripes::Horizontal) => {
+					queue.extend((0..self.cols()).map(|j| (x as u8, j as u8)));
+				}
+				Special::Striped(Stripes::Vertical) => {
+					queue.extend((0..self.lines()).map(|i| (i as u8, y as u8)));
+				}
+				Special::Bomb => {
+					let lines = x.saturating_sub(1)..usize::min(x + 2, self.lines());
+					for i in lines {
+						let cols = y.saturating_sub(1)..usize::min(y + 2, self.cols());
+						queue.extend(cols.map(|j| (i as u8, j as u8)));
+					}
+				}
+				Special::Hypercube => {
+					queue.extend(self.cells.indexed_iter()
+					                   .filter(|(_, c)| c.colour() == Some(gem))
+					                   .map(|((i, j), _)| (i as u8, j as u8)));
+				}
+			}
+			if special != Special::None {
+				crush.detonated.push(special);
+			}
+		}
+
+		// The newborn special gems replace the ones destroyed at their position.
+		for &((x, y), special) in &crush.created {
+			let (x, y) = (usize::from(x), usize::from(y));
+			hit[[x, y]] = false;
+			self.cells[[x, y]].special = special;
+		}
+
+		for ((x, y), &h) in hit.indexed_iter() {
+			if h {
+				crush.destroyed.push((x as u8, y as u8));
+			}
+		}
+		crush.fall = self.gravity(&crush.destroyed);
+
+		return Some(crush);
 	}
 
-	pub fn destroy_gems(&mut self, to_destroy: &[(u8, u8)]) {
+	/// Lists every swap of two neighbouring gems that check_matches() would accept.
+	/// An empty vector means the board is deadlocked and should be reshuffled.
+	pub fn legal_moves(&self) -> Vec<Move> {
+		let mut moves = Vec::new();
+		// Work on a copy, so that the swaps can be tried out without a &mut self.
+		let mut g = self.clone();
+		let (lines, cols) = self.size();
+
+		for x in 0..lines {
+			for y in 0..cols {
+				// Only look right and down, the other directions are the same swaps.
+				for &(x2, y2) in &[(x, y + 1), (x + 1, y)] {
+					if x2 >= lines || y2 >= cols {
+						continue;
+					}
+					// Swapping two identical gems changes nothing, unless they are hypercubes.
+					let cell = g.cell(x, y);
+					if cell == g.cell(x2, y2) && cell.special != Special::Hypercube {
+						continue;
+					}
+					g.permute((x, y), (x2, y2));
+					if g.check_matches((x, y), (x2, y2)) {
+						moves.push(((x, y), (x2, y2)));
+					}
+					g.permute((x, y), (x2, y2));
+				}
+			}
+		}
+		return moves;
+	}
 
-		// Not optimised.
+	/// Rearranges the gems of the grid so that there is no match on the board, but at least one
+	/// legal move. The gems are kept if possible. If they can't be laid out that way, new random
+	/// ones are drawn.
+	///
+	/// Fails if no such layout was found, which happens with tiny grids.
+	pub fn shuffle(&mut self) -> Result<(), &'static str> {
+		let mut pool: Vec<Cell> = self.cells.iter().copied().collect();
+
+		for attempt in 0..SHUFFLE_ATTEMPTS {
+			// Half of the attempts with the current gems, then with brand new ones.
+			if attempt == SHUFFLE_ATTEMPTS / 2 {
+				for cell in pool.iter_mut() {
+					*cell = Cell::from(Gem::random(&mut self.rng, self.colours));
+				}
+			}
+			pool.shuffle(&mut self.rng);
+			if self.lay_out(&pool) && !self.legal_moves().is_empty() {
+				return Ok(());
+			}
+		}
+		return Err("Couldn't reshuffle the grid into a playable state.");
+	}
 
-		let mut rng = rand::thread_rng();
+	/// Fills the grid line after line with the gems of the pool, each time picking the first
+	/// gem left that doesn't complete a match with its upper and left neighbours.
+	/// Returns false if it got stuck, in which case the grid is left in an unspecified state.
+	fn lay_out(&mut self, pool: &[Cell]) -> bool {
+		let mut pool = pool.to_vec();
+
+		for x in 0..self.lines() {
+			for y in 0..self.cols() {
+				let completes_match = |gem: Gem| {
+					(x >= 2 && self.get(x - 1, y) == gem && self.get(x - 2, y) == gem) ||
+					(y >= 2 && self.get(x, y - 1) == gem && self.get(x, y - 2) == gem)
+				};
+				match pool.iter().position(|cell| !completes_match(cell.gem)) {
+					Some(i) => self.cells[[x, y]] = pool.swap_remove(i),
+					None => return false,
+				}
+			}
+		}
+		return true;
+	}
 
+	/// Removes the gems at the given points, in any order, and lets the gems above them fall
+	/// in their place. New random gems fill the holes left at the top.
+	///
+	/// Each column is compacted in a single pass, from the bottom up.
+	/// Returns the moves of the gems that fell and the new gems, for the animations.
+	pub fn gravity(&mut self, to_destroy: &[(u8, u8)]) -> Fall {
+		let mut destroyed = Array2::<bool>::from_elem(self.cells.raw_dim(), false);
 		for &(x, y) in to_destroy {
-			// Destroy the gem by moving all that's up down on tile.
-			for up_x in (0..x).rev() { // from x-1 to 0.
-				self.0[[up_x as usize + 1, y.into()]] = self.0[[up_x.into(), y.into()]];
+			destroyed[[x.into(), y.into()]] = true;
+		}
+
+		let mut fall = Fall::default();
+		for y in 0..self.cols() {
+			// The lowest hole of the column, where the next surviving gem lands.
+			let mut bottom = self.lines();
+			for x in (0..self.lines()).rev() {
+				if destroyed[[x, y]] {
+					continue;
+				}
+				bottom -= 1;
+				if bottom != x {
+					self.cells[[bottom, y]] = self.cells[[x, y]];
+					fall.moves.push(((x as u8, y as u8), (bottom as u8, y as u8)));
+				}
+			}
+			// what's left above is filled with new gems.
+			for x in 0..bottom {
+				let gem = Gem::random(&mut self.rng, self.colours);
+				self.cells[[x, y]] = Cell::from(gem);
+				fall.spawns.push((y as u8, x as u8, gem));
 			}
-			// generate the new top gem
-			self.0[[0, y.into()]] = Gem::from_u8(rng.gen());
 		}
+		return fall;
 	}
 
 
@@ -294,7 +754,7 @@ mod test {
 
 	#[test]
 	fn gems() {
-		let mut gems = [Gem::Green, Gem::Red, Gem::Yellow, Gem::Blue].iter().cycle();
+		let mut gems = [Green, Red, Yellow, Blue, White, Pink, Cyan].iter().cycle();
 		for i in 0..u8::MAX {
 			//eprintln!("{} ~> {:?}", &i, &Gem::from_u8(i));
 			assert_eq!(Some(Gem::from_u8(i)).as_ref(), gems.next());
@@ -303,32 +763,32 @@ mod test {
 
 	#[test]
 	fn basic_grid() {
-		let g = Grid::new_from(5, 6, Gem::Green);
+		let g = Grid::new_from(5, 6, Gem::Green).unwrap();
 		assert_eq!(g.size(), (5, 6));
 		assert_eq!(g.lines(), 5);
 		assert_eq!(g.cols(), 6);
-		let g = Grid::new_from(13, 2, Gem::Green);
+		let g = Grid::new_from(13, 2, Gem::Green).unwrap();
 		assert_eq!(g.size(), (13, 2));
 		assert_eq!(g.lines(), 13);
 		assert_eq!(g.cols(), 2);
 
 		for size in 3..10 {
-			let mut g = Grid::new_from(size, size, Gem::Green);
+			let mut g = Grid::new_from(size, size, Gem::Green).unwrap();
 
 
-			g.0[[(size/2).into(), (size/2).into()]] = Gem::Red;
+			g.cells[[(size/2).into(), (size/2).into()]] = Cell::from(Gem::Red);
 			let t = g.match_border_from_point((size/2) as usize, (size/2) as usize);
 			assert_eq!(t, (size/2, size/2,size/2, size/2));
 
-			let g = Grid::new_from(size, size, Gem::Blue);
+			let g = Grid::new_from(size, size, Gem::Blue).unwrap();
 			let t = g.match_border_from_point(0, 0);
-
+			assert_eq!(t, (0, 0, size-1, size-1));
 		}
 
 		for lines in (3..9).chain(once(30)) {
 			for cols in (3..9).chain(once(30)) {
 
-				let mut g = Grid::new_from(lines, cols, Gem::Yellow);
+				let g = Grid::new_from(lines, cols, Gem::Yellow).unwrap();
 
 				for i in (0..(lines-1)).map(|a| a as usize) {
 					for j in (0..(cols-1)).map(|a| a as usize) {
@@ -343,7 +803,7 @@ mod test {
 	#[test]
 	fn matches() {
 
-		let g = Grid(ndarray::array![
+		let g = Grid::from(ndarray::array![
 			[Red,    Red,    Red,    Blue,   Blue,  Blue   ],
 			[Green,  Green,  Yellow, Yellow, Red,    Blue   ],
 			[Yellow, Yellow, Yellow, Yellow, Yellow, Blue   ],
@@ -357,7 +817,7 @@ mod test {
 						   assert!(matches.contains(tuple));
 		}
 
-		let g = Grid(ndarray::array![
+		let g = Grid::from(ndarray::array![
 			[Red,    Green, Green, Blue,   Blue],
 			[Red,    Red,   Green, Yellow, Blue],
 			[Yellow, Green, Blue,  Blue,   Yellow],
@@ -367,4 +827,212 @@ mod test {
 		assert!(matches.is_empty());
 
 	}
+
+	#[test]
+	fn legal_moves() {
+		let g = Grid::from(ndarray::array![
+			[Red,    Green, Red,   Blue],
+			[Yellow, Red,   Blue,  Yellow],
+			[Green,  Blue,  Green, Red],
+		]);
+		let moves = g.legal_moves();
+		// Moving the red gem up, or the green one down.
+		assert_eq!(moves, vec![((0, 1), (1, 1))]);
+
+		let g = Grid::from(ndarray::array![
+			[Red,    Green, Blue],
+			[Yellow, White, Pink],
+			[Blue,   Cyan,  Red],
+		]);
+		assert!(g.legal_moves().is_empty());
+	}
+
+	#[test]
+	fn shuffle() {
+		for seed in 0..32 {
+			let mut g = Grid::new_rand(8, 6, 7, seed).unwrap();
+			g.shuffle().unwrap();
+
+			let (points, _) = g.get_all_matches();
+			assert!(points.is_empty());
+			assert!(!g.legal_moves().is_empty());
+		}
+
+		// A 2×2 grid never has a legal move.
+		let mut g = Grid::new_rand(2, 2, 7, 7).unwrap();
+		assert!(g.shuffle().is_err());
+	}
+
+	#[test]
+	fn too_small() {
+		assert!(Grid::new_from(1, 5, Gem::Red).is_err());
+		assert!(Grid::new_rand(5, 0, 7, 7).is_err());
+		assert!(Grid::new_rand(2, 2, 7, 7).is_ok());
+	}
+
+	#[test]
+	fn colours() {
+		assert!(Grid::new_rand(8, 8, 3, 7).is_err());
+		assert!(Grid::new_rand(8, 8, 8, 7).is_err());
+
+		for colours in MIN_COLOURS..=MAX_COLOURS {
+			let mut g = Grid::new_rand(10, 10, colours, 7).unwrap();
+			let allowed = |g: &Grid| g.cells.iter().all(|c| (c.gem as u8) < colours);
+			assert!(allowed(&g));
+			while g.crush(&[]).is_some() {}
+			assert!(allowed(&g));
+			g.shuffle().unwrap();
+			assert!(allowed(&g));
+		}
+	}
+
+	#[test]
+	fn striped() {
+		let mut g = Grid::from(ndarray::array![
+			[Green, Red,   Green, Blue],
+			[Red,   Blue,  Red,   Red],
+			[Cyan,  White, Cyan,  Pink],
+		]);
+		// The player moves the red gem down, four reds are lined up.
+		g.permute((0, 1), (1, 1));
+		let crush = g.crush(&[(0, 1), (1, 1)]).unwrap();
+		assert_eq!(crush.matches, vec![(Red, 4)]);
+		assert_eq!(crush.created, vec![((1, 1), Special::Striped(Stripes::Horizontal))]);
+		assert_eq!(crush.destroyed, vec![(1, 0), (1, 2), (1, 3)]);
+		assert!(crush.detonated.is_empty());
+		// The striped gem fell down with the rest of the column.
+		assert_eq!(g.cell(1, 1), Cell { gem: Red, special: Special::Striped(Stripes::Horizontal) });
+
+		// Now, a match with a striped gem in it clears the whole line.
+		let mut g = Grid::from(ndarray::array![
+			[Green, Yellow, Cyan,  Blue],
+			[Red,   Red,    Red,   Yellow],
+			[Cyan,  Blue,   White, Pink],
+		]);
+		g.cells[[1, 2]].special = Special::Striped(Stripes::Vertical);
+		g.cells[[2, 2]].special = Special::Striped(Stripes::Horizontal);
+		let crush = g.crush(&[]).unwrap();
+		// The first one is matched and clears its column, the second one is caught in the blast
+		// and clears the last line.
+		assert_eq!(crush.matches, vec![(Red, 3)]);
+		assert_eq!(crush.destroyed.len(), 3 + 2 + 3);
+		assert_eq!(crush.detonated.len(), 2);
+		assert!(crush.created.is_empty());
+	}
+
+	#[test]
+	fn bomb() {
+		let mut g = Grid::from(ndarray::array![
+			[Red, Blue,  Green, Yellow],
+			[Red, Green, Blue,  Cyan],
+			[Red, Red,   Red,   Pink],
+		]);
+		let crush = g.crush(&[]).unwrap();
+		assert_eq!(crush.matches, vec![(Red, 3), (Red, 3)]);
+		assert_eq!(crush.created, vec![((2, 0), Special::Bomb)]);
+		assert_eq!(crush.destroyed.len(), 4);
+		assert_eq!(g.cell(2, 0), Cell { gem: Red, special: Special::Bomb });
+
+		// The bomb goes off when matched and clears the 3×3 square around it.
+		let mut g = Grid::from(ndarray::array![
+			[Cyan,  Blue,  Green, Yellow],
+			[Green, Red,   Blue,  Cyan],
+			[Pink,  Red,   Cyan,  Pink],
+			[White, Red,   Blue,  Blue],
+		]);
+		g.cells[[2, 1]].special = Special::Bomb;
+		let crush = g.crush(&[]).unwrap();
+		assert_eq!(crush.detonated, vec![Special::Bomb]);
+		assert_eq!(crush.destroyed, vec![(1, 0), (1, 1), (1, 2), (2, 0), (2, 1), (2, 2),
+		                                 (3, 0), (3, 1), (3, 2)]);
+	}
+
+	#[test]
+	fn hypercube() {
+		let mut g = Grid::from(ndarray::array![
+			[Blue,  Yellow, Green, Yellow, Blue],
+			[Green, Green,  Red,   Green,  Green],
+			[Pink,  Blue,   Cyan,  Pink,   Cyan],
+		]);
+		// The red gem moves up, five greens are lined up.
+		g.permute((0, 2), (1, 2));
+		assert!(g.check_matches((0, 2), (1, 2)));
+		let crush = g.crush(&[(0, 2), (1, 2)]).unwrap();
+		assert_eq!(crush.matches, vec![(Green, 5)]);
+		assert_eq!(crush.created, vec![((1, 2), Special::Hypercube)]);
+		assert_eq!(g.cell(1, 2).special, Special::Hypercube);
+		// It lies between the red and the cyan gem now.
+		assert_eq!(g.get(0, 2), Red);
+
+		// A hypercube matches with nothing, but can be swapped with anything.
+		let mut g = Grid::from(ndarray::array![
+			[Blue,  Green, Blue,  Yellow, Blue],
+			[Green, Pink,  Red,   Red,    White],
+			[Pink,  Blue,  Cyan,  Pink,   Cyan],
+		]);
+		g.cells[[1, 1]] = Cell { gem: Red, special: Special::Hypercube };
+		assert!(g.get_all_matches().0.is_empty());
+		assert!(g.check_matches((1, 1), (2, 1)));
+		g.permute((1, 1), (2, 1));
+		let crush = g.crush(&[(1, 1), (2, 1)]).unwrap();
+		// All the blue ones are gone, along with the hypercube.
+		assert_eq!(crush.destroyed, vec![(0, 0), (0, 2), (0, 4), (1, 1), (2, 1)]);
+		assert_eq!(crush.detonated, vec![Special::Hypercube]);
+		assert!(crush.matches.is_empty());
+	}
+
+	#[test]
+	fn seeded() {
+		let mut a = Grid::new_rand(8, 6, 7, 1234).unwrap();
+		let mut b = Grid::new_rand(8, 6, 7, 1234).unwrap();
+		assert_eq!(a.cells, b.cells);
+		assert_eq!(a.seed(), 1234);
+		assert_ne!(a.cells, Grid::new_rand(8, 6, 7, 4321).unwrap().cells);
+
+		// The refills are the same too.
+		while let (Some(ca), Some(cb)) = (a.crush(&[]), b.crush(&[])) {
+			assert_eq!(ca.destroyed, cb.destroyed);
+			assert_eq!(a.cells, b.cells);
+		}
+		a.shuffle().unwrap();
+		b.shuffle().unwrap();
+		assert_eq!(a.cells, b.cells);
+
+		// A generator restored from its seed and position goes on the same way.
+		let mut c = Grid::from_cells(a.cells.clone(), a.colours(), a.seed());
+		c.set_rng_position(a.rng_position());
+		a.gravity(&[(3, 3), (4, 3)]);
+		c.gravity(&[(3, 3), (4, 3)]);
+		assert_eq!(a.cells, c.cells);
+	}
+
+	#[test]
+	fn gravity() {
+		let mut g = Grid::from(ndarray::array![
+			[Red,    Green, Blue  ],
+			[Yellow, Green, Blue  ],
+			[Pink,   Cyan,  White ],
+			[Red,    Green, Blue  ],
+		]);
+		// Two holes in the first column, one under the other in the second.
+		let fall = g.gravity(&[(2, 0), (1, 1), (1, 0)]);
+		// The gems above the holes fall in them, and the grid refills from its seed, the same
+		// way every time.
+		assert_eq!(fall, Fall {
+			moves: vec![((0, 0), (2, 0)), ((0, 1), (1, 1))],
+			spawns: vec![(0, 0, White), (0, 1, White), (1, 0, Pink)],
+		});
+		assert_eq!(fall.drops(g.size()), ndarray::array![
+			[2, 1, 0],
+			[2, 1, 0],
+			[2, 0, 0],
+			[0, 0, 0],
+		]);
+		assert_eq!(g.cells.mapv(|cell| cell.gem), ndarray::array![
+			[White, Pink,  Blue  ],
+			[White, Green, Blue  ],
+			[Red,   Cyan,  White ],
+			[Red,   Green, Blue  ],
+		]);
+	}
 }
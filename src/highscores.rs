@@ -0,0 +1,255 @@
+/*
+ * The best scores of each mode and board size, kept on disk between the games.
+ */
+
+use crate::engine::Mode;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The first line of the file. Bump the version whenever the format changes.
+const HEADER: &str = "bejeweled-term scores 1";
+/// How many scores each table keeps.
+pub const TOP: usize = 10;
+
+/// One game in the table.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+	pub mode: Mode,
+	pub height: u8,
+	pub width: u8,
+	pub score: u64,
+	/// The longest cascade of the game.
+	pub combo: u32,
+	pub seed: u64,
+	/// When the game ended, in seconds since the Unix epoch.
+	pub date: u64,
+	pub name: String,
+}
+
+impl Entry {
+	/// A game that ends now. The name is cleaned of anything that would break the file.
+	pub fn new(mode: Mode, (height, width): (u8, u8), score: u64, combo: u32, seed: u64, name: &str) -> Entry {
+		let date = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+		let name: String = name.chars().map(|c| if c.is_control() {' '} else {c}).collect();
+		let name = match name.trim() {
+			"" => String::from("anonymous"),
+			n => n.to_string(),
+		};
+		Entry { mode, height, width, score, combo, seed, date, name }
+	}
+
+	/// One line of a table shown to the player.
+	pub fn line(&self) -> String {
+		format!("{:>7}  ×{:<2} {:<12.12} {}  seed {}", self.score, self.combo, self.name, date(self.date), self.seed)
+	}
+}
+
+/// The tables of every mode and board size, in a single file.
+///
+///     bejeweled-term scores 1
+///     blitz 8 6 4200 3 1234 1792195200 Alice
+///
+/// Each line holds the mode, the height and width of the board, the score, the best combo,
+/// the seed, the date and the name of the player, which may have spaces in it.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct HighScores {
+	entries: Vec<Entry>,
+}
+
+/// Where the high scores are kept, next to the save file.
+pub fn default_path() -> Option<PathBuf> {
+	crate::save::data_dir().map(|dir| dir.join("scores"))
+}
+
+impl HighScores {
+
+	/// Reads the tables. There are none yet if the file doesn't exist.
+	pub fn read(path: &Path) -> Result<HighScores, &'static str> {
+		if !path.exists() {
+			return Ok(HighScores::default());
+		}
+		let s = std::fs::read_to_string(path).map_err(|_| "Couldn't read the high scores.")?;
+		HighScores::parse(&s)
+	}
+
+	/// Reads the tables at their usual place.
+	pub fn read_default() -> Result<HighScores, &'static str> {
+		let path = default_path().ok_or("No place to keep the high scores, HOME is not set.")?;
+		HighScores::read(&path)
+	}
+
+	pub fn write(&self, path: &Path) -> Result<(), &'static str> {
+		if let Some(dir) = path.parent() {
+			std::fs::create_dir_all(dir).map_err(|_| "Couldn't create the directory of the high scores.")?;
+		}
+		std::fs::write(path, self.to_string()).map_err(|_| "Couldn't write the high scores.")
+	}
+
+	pub fn parse(s: &str) -> Result<HighScores, &'static str> {
+		let mut lines = s.lines();
+		if lines.next() != Some(HEADER) {
+			return Err("The high scores file is not one, or it comes from another version of the game.");
+		}
+		let mut scores = HighScores::default();
+		for line in lines.filter(|l| !l.trim().is_empty()) {
+			scores.entries.push(parse_entry(line).ok_or("Bad line in the high scores file.")?);
+		}
+		return Ok(scores);
+	}
+
+	/// Adds a game to its table. Returns its rank in the table, from 0, if it made it there.
+	pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+		let rank = self.table(entry.mode, (entry.height, entry.width))
+		               .iter()
+		               .take_while(|e| e.score >= entry.score)
+		               .count();
+		if rank >= TOP {
+			return None;
+		}
+		let (mode, height, width) = (entry.mode, entry.height, entry.width);
+		self.entries.push(entry);
+		// keep the entries sorted, the best first, and drop the ones pushed out of their table.
+		self.entries.sort_by_key(|e| std::cmp::Reverse(e.score));
+		let mut kept = 0;
+		self.entries.retain(|e| {
+			if (e.mode, e.height, e.width) != (mode, height, width) {
+				return true;
+			}
+			kept += 1;
+			kept <= TOP
+		});
+		return Some(rank);
+	}
+
+	/// The table of a mode and board size, the best score first.
+	pub fn table(&self, mode: Mode, (height, width): (u8, u8)) -> Vec<&Entry> {
+		let mut table: Vec<&Entry> = self.entries.iter()
+		                                 .filter(|e| (e.mode, e.height, e.width) == (mode, height, width))
+		                                 .collect();
+		// stable, so that the older of two equal scores stays in front.
+		table.sort_by_key(|e| std::cmp::Reverse(e.score));
+		table.truncate(TOP);
+		return table;
+	}
+
+	/// The modes and board sizes that have a table.
+	pub fn tables(&self) -> Vec<(Mode, (u8, u8))> {
+		let mut tables: Vec<(Mode, (u8, u8))> = Vec::new();
+		for e in &self.entries {
+			if !tables.contains(&(e.mode, (e.height, e.width))) {
+				tables.push((e.mode, (e.height, e.width)));
+			}
+		}
+		return tables;
+	}
+
+	/// A table as lines of text, with a title. The entry at `highlight` is marked.
+	pub fn lines(&self, mode: Mode, size: (u8, u8), highlight: Option<usize>) -> Vec<String> {
+		let mut lines = vec![format!("High scores, {} {}×{}", mode.name(), size.0, size.1)];
+		let table = self.table(mode, size);
+		if table.is_empty() {
+			lines.push(String::from("No game yet."));
+		}
+		for (i, e) in table.iter().enumerate() {
+			let mark = if highlight == Some(i) {'>'} else {' '};
+			lines.push(format!("{}{:>2}. {}", mark, i + 1, e.line()));
+		}
+		return lines;
+	}
+}
+
+impl fmt::Display for HighScores {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{}", HEADER)?;
+		for e in &self.entries {
+			writeln!(f, "{} {} {} {} {} {} {} {}", e.mode.name(), e.height, e.width, e.score, e.combo, e.seed, e.date, e.name)?;
+		}
+		Ok(())
+	}
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+	let mut words = line.splitn(8, ' ');
+	let mut next = || words.next();
+	return Some(Entry {
+		mode: Mode::from_name(next()?)?,
+		height: next()?.parse().ok()?,
+		width: next()?.parse().ok()?,
+		score: next()?.parse().ok()?,
+		combo: next()?.parse().ok()?,
+		seed: next()?.parse().ok()?,
+		date: next()?.parse().ok()?,
+		name: next()?.to_string(),
+	});
+}
+
+/// The date, as year-month-day, of a number of seconds since the Unix epoch.
+fn date(secs: u64) -> String {
+	// Howard Hinnant's days_from_civil, the other way round.
+	let days = (secs / 86400) as i64 + 719468;
+	let era = days.div_euclid(146097);
+	let doe = days.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 {mp + 3} else {mp - 9};
+	let year = yoe + era * 400 + if month <= 2 {1} else {0};
+	format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn entry(mode: Mode, score: u64, name: &str) -> Entry {
+		Entry { mode, height: 8, width: 6, score, combo: 2, seed: 42, date: 1_792_195_200, name: name.to_string() }
+	}
+
+	#[test]
+	fn tables() {
+		let mut scores = HighScores::default();
+		for i in 0..TOP as u64 {
+			assert_eq!(scores.insert(entry(Mode::Blitz, 100 * (i + 1), "bob")), Some(0));
+		}
+		assert_eq!(scores.insert(entry(Mode::Blitz, 50, "too low")), None);
+		assert_eq!(scores.insert(entry(Mode::Blitz, 550, "alice")), Some(5));
+		assert_eq!(scores.insert(entry(Mode::Moves, 10, "carol")), Some(0));
+
+		let table = scores.table(Mode::Blitz, (8, 6));
+		assert_eq!(table.len(), TOP);
+		assert_eq!(table[0].score, 1000);
+		assert_eq!(table[5].name, "alice");
+		assert_eq!(table[TOP - 1].score, 200);
+		assert!(scores.table(Mode::Blitz, (8, 8)).is_empty());
+		assert_eq!(scores.tables(), [(Mode::Blitz, (8, 6)), (Mode::Moves, (8, 6))]);
+
+		let lines = scores.lines(Mode::Moves, (8, 6), Some(0));
+		assert_eq!(lines[0], "High scores, moves 8×6");
+		assert_eq!(lines[1], "> 1.      10  ×2  carol        2026-10-17  seed 42");
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut scores = HighScores::default();
+		scores.insert(entry(Mode::Levels, 12000, "Jean-Pierre de la Tour"));
+		scores.insert(Entry::new(Mode::Blitz, (6, 5), 800, 4, 7, "  new\nline "));
+		let text = scores.to_string();
+		assert!(text.contains("levels 8 6 12000 2 42 1792195200 Jean-Pierre de la Tour\n"));
+		assert!(text.contains(" new line\n"));
+		assert_eq!(HighScores::parse(&text).unwrap(), scores);
+
+		assert!(HighScores::parse("bejeweled-term scores 0\n").is_err());
+		assert!(HighScores::parse(&format!("{}\nzen 8 6 1 1 1 1 me\n", HEADER)).is_err());
+		assert!(HighScores::parse(&format!("{}\nblitz 8 6 1 1 1\n", HEADER)).is_err());
+	}
+
+	#[test]
+	fn dates() {
+		assert_eq!(date(0), "1970-01-01");
+		assert_eq!(date(951_782_400), "2000-02-29");
+		assert_eq!(date(1_792_195_200), "2026-10-17");
+	}
+}
@@ -0,0 +1,343 @@
+/*
+ * Key bindings: what the keys pressed by the player mean to the game.
+ */
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// A key, as the game sees it, whatever terminal library read it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+	Char(char),
+	Arrow(Direction),
+	/// An arrow with shift held down.
+	ShiftArrow(Direction),
+	Enter,
+	Escape,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+	Up,
+	Left,
+	Down,
+	Right,
+}
+
+impl Direction {
+	pub const ALL: [Direction; 4] = [Direction::Up, Direction::Left, Direction::Down, Direction::Right];
+
+	/// The neighbour of the tile (x, y) in that direction, on a board of the given size.
+	/// None if it would be off the board.
+	pub fn neighbour(self, (x, y): (usize, usize), (lines, cols): (usize, usize)) -> Option<(usize, usize)> {
+		match self {
+			Direction::Up if x != 0 => Some((x - 1, y)),
+			Direction::Left if y != 0 => Some((x, y - 1)),
+			Direction::Down if x + 1 < lines => Some((x + 1, y)),
+			Direction::Right if y + 1 < cols => Some((x, y + 1)),
+			_ => None,
+		}
+	}
+
+	/// The direction that goes from the tile a to its neighbour b, if they are neighbours.
+	pub fn between(a: (usize, usize), b: (usize, usize)) -> Option<Direction> {
+		Direction::ALL.iter().copied().find(|d| d.neighbour(a, (usize::MAX, usize::MAX)) == Some(b))
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Direction::Up => "up",
+			Direction::Left => "left",
+			Direction::Down => "down",
+			Direction::Right => "right",
+		}
+	}
+}
+
+/// What the player wants to do.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+	/// Move the cursor. The Term takes care of it, the game never sees that one.
+	Cursor(Direction),
+	/// Swap the gem under the cursor with its neighbour.
+	Swap(Direction),
+	/// Pick up the gem under the cursor, the next direction swaps it. The Term takes care of it.
+	Select,
+	/// Put the picked up gem back down. The Term takes care of it too.
+	Cancel,
+	Hint,
+	/// Take back the last move, or play again the last move taken back.
+	Undo,
+	Redo,
+	/// Show or hide the log.
+	Log,
+	/// Show the high scores of the mode.
+	Scores,
+	/// Play again, once the game is over.
+	Restart,
+	/// Save and exit, or give up in the modes that aren't saved.
+	Quit,
+}
+
+impl Action {
+	/// Every action, in the order of the key file.
+	const ALL: [Action; 17] = [
+		Action::Cursor(Direction::Up), Action::Cursor(Direction::Left),
+		Action::Cursor(Direction::Down), Action::Cursor(Direction::Right),
+		Action::Swap(Direction::Up), Action::Swap(Direction::Left),
+		Action::Swap(Direction::Down), Action::Swap(Direction::Right),
+		Action::Select, Action::Cancel, Action::Hint, Action::Undo, Action::Redo, Action::Log,
+		Action::Scores, Action::Restart, Action::Quit,
+	];
+
+	/// The name of the action in the key file.
+	fn name(self) -> String {
+		match self {
+			Action::Cursor(d) => d.name().to_string(),
+			Action::Swap(d) => format!("swap-{}", d.name()),
+			Action::Select => String::from("select"),
+			Action::Cancel => String::from("cancel"),
+			Action::Hint => String::from("hint"),
+			Action::Undo => String::from("undo"),
+			Action::Redo => String::from("redo"),
+			Action::Log => String::from("log"),
+			Action::Scores => String::from("scores"),
+			Action::Restart => String::from("restart"),
+			Action::Quit => String::from("quit"),
+		}
+	}
+}
+
+/// Sets of bindings for the usual keyboard layouts. The arrows always move the cursor,
+/// enter and space pick up a gem, escape puts it back down, u undoes, r redoes,
+/// t shows the high scores and n plays again once the game is over.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+	/// Z/Q/S/D to swap, W to quit.
+	Azerty,
+	/// W/A/S/D to swap, X to quit.
+	Qwerty,
+	/// h/j/k/l to move, H/J/K/L to swap, ? for a hint, o for the log and q to quit.
+	Vim,
+	/// Shift and an arrow to swap, q to quit.
+	Arrows,
+}
+
+/// Which action each key does. A key does one action at most, an action may have several keys.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyMap {
+	bindings: Vec<(Key, Action)>,
+}
+
+impl KeyMap {
+
+	pub fn preset(preset: Preset) -> KeyMap {
+		use Direction::*;
+		let mut map = KeyMap { bindings: Vec::new() };
+		for &d in &Direction::ALL {
+			map.bind(Key::Arrow(d), Action::Cursor(d));
+		}
+		map.bind(Key::Enter, Action::Select);
+		map.bind(Key::Char(' '), Action::Select);
+		map.bind(Key::Escape, Action::Cancel);
+		map.bind(Key::Char('u'), Action::Undo);
+		map.bind(Key::Char('r'), Action::Redo);
+		map.bind(Key::Char('t'), Action::Scores);
+		map.bind(Key::Char('n'), Action::Restart);
+		let (swaps, hint, log, quit) = match preset {
+			Preset::Azerty => ([Key::Char('z'), Key::Char('q'), Key::Char('s'), Key::Char('d')], 'h', 'l', 'w'),
+			Preset::Qwerty => ([Key::Char('w'), Key::Char('a'), Key::Char('s'), Key::Char('d')], 'h', 'l', 'x'),
+			Preset::Vim => {
+				for &(c, d) in &[('k', Up), ('h', Left), ('j', Down), ('l', Right)] {
+					map.bind(Key::Char(c), Action::Cursor(d));
+				}
+				([Key::Char('K'), Key::Char('H'), Key::Char('J'), Key::Char('L')], '?', 'o', 'q')
+			}
+			Preset::Arrows => ([Key::ShiftArrow(Up), Key::ShiftArrow(Left), Key::ShiftArrow(Down), Key::ShiftArrow(Right)], 'h', 'l', 'q'),
+		};
+		for (&key, &d) in swaps.iter().zip(&Direction::ALL) {
+			map.bind(key, Action::Swap(d));
+		}
+		map.bind(Key::Char(hint), Action::Hint);
+		map.bind(Key::Char(log), Action::Log);
+		map.bind(Key::Char(quit), Action::Quit);
+		return map;
+	}
+
+	/// Make the key do that action, and nothing else.
+	pub fn bind(&mut self, key: Key, action: Action) {
+		self.bindings.retain(|&(k, _)| k != key);
+		self.bindings.push((key, action));
+	}
+
+	pub fn action(&self, key: Key) -> Option<Action> {
+		self.bindings.iter().find(|&&(k, _)| k == key).map(|&(_, a)| a)
+	}
+
+	/// The first key bound to the action, if there is one.
+	pub fn key(&self, action: Action) -> Option<Key> {
+		self.bindings.iter().find(|&&(_, a)| a == action).map(|&(k, _)| k)
+	}
+
+	/// The one-line help shown under the board, with the keys of this map.
+	pub fn help(&self, quit: &str) -> String {
+		let name = |a| self.key(a).map_or_else(|| String::from("(unbound)"), |k| k.to_string());
+		format!("Press {} to {}, {} for a hint, {} for the log.", name(Action::Quit), quit, name(Action::Hint), name(Action::Log))
+	}
+
+	/// Applies a key file on top of the map. Each line holds an action and the keys that do it,
+	/// which replace its previous keys. A `preset NAME` line starts over from a preset.
+	///
+	///     # Comments start with a hash.
+	///     preset qwerty
+	///     swap-up w shift-up
+	///     quit escape
+	///
+	/// The keys are single characters, or up, left, down, right, shift-up, shift-left,
+	/// shift-down, shift-right, enter, escape, space.
+	pub fn parse(&mut self, text: &str) -> Result<(), String> {
+		for (i, line) in text.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or("");
+			let mut words = line.split_whitespace();
+			let first = match words.next() {
+				Some(w) => w,
+				None => continue,
+			};
+			let error = |what: String| format!("line {} of the key file: {}", i + 1, what);
+
+			if first == "preset" {
+				let name = words.next().unwrap_or("");
+				*self = KeyMap::preset(parse_preset(name).map_err(error)?);
+				continue;
+			}
+			let action = Action::ALL.iter().copied().find(|a| a.name() == first)
+			                        .ok_or_else(|| error(format!("unknown action '{}'", first)))?;
+			let keys = words.map(parse_key).collect::<Result<Vec<Key>, String>>().map_err(error)?;
+			self.bindings.retain(|&(_, a)| a != action);
+			for key in keys {
+				self.bind(key, action);
+			}
+		}
+		return Ok(());
+	}
+}
+
+impl Default for KeyMap {
+	fn default() -> Self {
+		KeyMap::preset(Preset::Azerty)
+	}
+}
+
+impl fmt::Display for Key {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Key::Char(' ') => write!(f, "space"),
+			Key::Char(c) => write!(f, "'{}'", c),
+			Key::Arrow(d) => write!(f, "{}", d.name()),
+			Key::ShiftArrow(d) => write!(f, "shift-{}", d.name()),
+			Key::Enter => write!(f, "enter"),
+			Key::Escape => write!(f, "escape"),
+		}
+	}
+}
+
+pub fn parse_preset(s: &str) -> Result<Preset, String> {
+	match s {
+		"azerty" => Ok(Preset::Azerty),
+		"qwerty" => Ok(Preset::Qwerty),
+		"vim" => Ok(Preset::Vim),
+		"arrows" => Ok(Preset::Arrows),
+		_ => Err(format!("unknown key preset '{}', expected azerty, qwerty, vim or arrows", s)),
+	}
+}
+
+fn parse_key(s: &str) -> Result<Key, String> {
+	let mut chars = s.chars();
+	if let (Some(c), None) = (chars.next(), chars.next()) {
+		return Ok(Key::Char(c));
+	}
+	let key = match s {
+		"enter" => Key::Enter,
+		"escape" => Key::Escape,
+		"space" => Key::Char(' '),
+		_ => {
+			let (shift, name) = match s.strip_prefix("shift-") {
+				Some(name) => (true, name),
+				None => (false, s),
+			};
+			let d = Direction::ALL.iter().copied().find(|d| d.name() == name)
+			                      .ok_or_else(|| format!("unknown key '{}'", s))?;
+			if shift {Key::ShiftArrow(d)} else {Key::Arrow(d)}
+		}
+	};
+	return Ok(key);
+}
+
+/// Where the player may change the key bindings:
+/// $XDG_CONFIG_HOME/bejeweled-term/keys, or ~/.config/bejeweled-term/keys if that's not set.
+pub fn default_path() -> Option<PathBuf> {
+	let base = match std::env::var_os("XDG_CONFIG_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+	};
+	Some(base.join("bejeweled-term").join("keys"))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn presets() {
+		let azerty = KeyMap::default();
+		assert_eq!(azerty.action(Key::Char('z')), Some(Action::Swap(Direction::Up)));
+		assert_eq!(azerty.action(Key::Char('w')), Some(Action::Quit));
+		assert_eq!(azerty.action(Key::Arrow(Direction::Left)), Some(Action::Cursor(Direction::Left)));
+		assert_eq!(azerty.action(Key::Char(' ')), Some(Action::Select));
+		assert_eq!(azerty.action(Key::Escape), Some(Action::Cancel));
+		assert_eq!(azerty.action(Key::Char('u')), Some(Action::Undo));
+		assert_eq!(azerty.action(Key::Char('t')), Some(Action::Scores));
+		assert_eq!(azerty.key(Action::Restart), Some(Key::Char('n')));
+		assert_eq!(azerty.help("save and exit"), "Press 'w' to save and exit, 'h' for a hint, 'l' for the log.");
+
+		let qwerty = KeyMap::preset(Preset::Qwerty);
+		assert_eq!(qwerty.action(Key::Char('w')), Some(Action::Swap(Direction::Up)));
+		assert_eq!(qwerty.action(Key::Char('a')), Some(Action::Swap(Direction::Left)));
+
+		let vim = KeyMap::preset(Preset::Vim);
+		assert_eq!(vim.action(Key::Char('h')), Some(Action::Cursor(Direction::Left)));
+		assert_eq!(vim.action(Key::Char('J')), Some(Action::Swap(Direction::Down)));
+		assert_eq!(vim.action(Key::Char('l')), Some(Action::Cursor(Direction::Right)));
+
+		let arrows = KeyMap::preset(Preset::Arrows);
+		assert_eq!(arrows.action(Key::ShiftArrow(Direction::Right)), Some(Action::Swap(Direction::Right)));
+		assert_eq!(arrows.action(Key::Char('z')), None);
+	}
+
+	#[test]
+	fn key_file() {
+		let mut map = KeyMap::default();
+		map.parse("# my keys\npreset qwerty\n\nswap-up w shift-up  # both\nquit escape\nhint space\nrestart enter\n").unwrap();
+		assert_eq!(map.action(Key::Char('w')), Some(Action::Swap(Direction::Up)));
+		assert_eq!(map.action(Key::ShiftArrow(Direction::Up)), Some(Action::Swap(Direction::Up)));
+		assert_eq!(map.action(Key::Char('x')), None);
+		assert_eq!(map.action(Key::Escape), Some(Action::Quit));
+		assert_eq!(map.action(Key::Enter), Some(Action::Restart));
+		assert_eq!(map.help("quit"), "Press escape to quit, space for a hint, 'l' for the log.");
+
+		for bad in &["jump j", "hint shift-f1", "preset dvorak", "quit ctrl-c"] {
+			assert!(KeyMap::default().parse(bad).is_err(), "{:?}", bad);
+		}
+		assert_eq!(KeyMap::default().parse("hint h\nfly f").unwrap_err(), "line 2 of the key file: unknown action 'fly'");
+	}
+
+	#[test]
+	fn directions() {
+		assert_eq!(Direction::Up.neighbour((0, 3), (4, 4)), None);
+		assert_eq!(Direction::Right.neighbour((0, 2), (4, 4)), Some((0, 3)));
+		assert_eq!(Direction::Right.neighbour((0, 3), (4, 4)), None);
+		assert_eq!(Direction::between((2, 2), (3, 2)), Some(Direction::Down));
+		assert_eq!(Direction::between((2, 2), (3, 3)), None);
+		assert_eq!(Direction::between((0, 0), (0, 0)), None);
+	}
+}
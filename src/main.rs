@@ -1,27 +1,55 @@
-//!
-//!
-//!
+//! Bejeweled-term, a match-the-three game in the terminal.
 
-// game logic and rules
+// the solver that looks for the best moves
+mod ai;
+// command-line arguments
+mod cli;
+// the rules of the game, without any interface
+mod engine;
+// what the terminal user interface draws on: ncurses or plain escape sequences
+mod frontend;
+// configuration and main loop of a game
 mod game;
 // basic operation on the jewel grid
 mod grid;
+// the best scores of each mode
+mod highscores;
+// key bindings
+mod keys;
+// messages to the player
+mod message;
+// save and resume a game in progress
+mod save;
+// scoring rules
+mod score;
 // control the terminal user interface
 mod term;
 
 fn main() {
 
-	// Set a custom panic that first attempts to uninitialize NCurses before printing any message.
+	// Set a custom panic that first attempts to give the terminal back before printing any message.
 	let old_hook = std::panic::take_hook();
 	std::panic::set_hook(Box::new(move |panic_info| {
-		term::free_ncurses();
+		frontend::restore_terminal();
 		old_hook(panic_info);
 		std::process::exit(1); // for now, panicking terminates the process.
 	}));
 
-	let mut conf = game::Config::default();
-	conf.height = 8;
-	conf.width = 6;
-	game::main(&conf).unwrap();
+	let conf = match cli::parse(std::env::args().skip(1)) {
+		Ok(Some(conf)) => conf,
+		Ok(None) => {
+			print!("{}", cli::HELP);
+			return;
+		}
+		Err(e) => {
+			eprintln!("error: {}\n\nFor more information, try '--help'.", e);
+			std::process::exit(2);
+		}
+	};
+
+	if let Err(e) = game::main(&conf) {
+		eprintln!("error: {}", e);
+		std::process::exit(1);
+	}
 
 }
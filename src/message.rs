@@ -0,0 +1,120 @@
+/*
+ * Messages to the player: what just happened, and a log of everything that happened.
+ */
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// How long a message stays on the message line.
+const LIFETIME: Duration = Duration::from_secs(5);
+// How many messages the log remembers.
+const CAPACITY: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+	pub text: String,
+	pub severity: Severity,
+	pub time: Instant,
+}
+
+/// A bounded log of messages. The newest one is shown to the player until it expires,
+/// all of them are kept in the log until they are pushed out by newer ones.
+#[derive(Debug)]
+pub struct Messages {
+	log: VecDeque<Message>,
+	/// When the log was created, the timestamps are shown relative to it.
+	start: Instant,
+}
+
+impl Messages {
+
+	pub fn new() -> Messages {
+		Messages {
+			log: VecDeque::with_capacity(CAPACITY),
+			start: Instant::now(),
+		}
+	}
+
+	pub fn push<S: Into<String>>(&mut self, severity: Severity, text: S) {
+		self.push_at(severity, text, Instant::now());
+	}
+
+	fn push_at<S: Into<String>>(&mut self, severity: Severity, text: S, time: Instant) {
+		if self.log.len() == CAPACITY {
+			self.log.pop_front();
+		}
+		self.log.push_back(Message { text: text.into(), severity, time });
+	}
+
+	pub fn info<S: Into<String>>(&mut self, text: S) {
+		self.push(Severity::Info, text);
+	}
+
+	pub fn warn<S: Into<String>>(&mut self, text: S) {
+		self.push(Severity::Warning, text);
+	}
+
+	pub fn error<S: Into<String>>(&mut self, text: S) {
+		self.push(Severity::Error, text);
+	}
+
+	/// The newest message, unless it has expired.
+	pub fn current(&self) -> Option<&Message> {
+		self.current_at(Instant::now())
+	}
+
+	fn current_at(&self, now: Instant) -> Option<&Message> {
+		self.log.back().filter(|m| now.saturating_duration_since(m.time) < LIFETIME)
+	}
+
+	/// All the messages still in the log, the oldest first.
+	pub fn log(&self) -> impl DoubleEndedIterator<Item = &Message> + ExactSizeIterator {
+		self.log.iter()
+	}
+
+	/// How long after the creation of the log the message was pushed.
+	pub fn timestamp(&self, m: &Message) -> Duration {
+		m.time.saturating_duration_since(self.start)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn expiry() {
+		let mut messages = Messages::new();
+		let t0 = Instant::now();
+		assert!(messages.current_at(t0).is_none());
+
+		messages.push_at(Severity::Info, "hello", t0);
+		messages.push_at(Severity::Warning, String::from("careful"), t0 + Duration::from_secs(1));
+		let current = messages.current_at(t0 + Duration::from_secs(2)).unwrap();
+		assert_eq!(current.text, "careful");
+		assert_eq!(current.severity, Severity::Warning);
+
+		// Gone from the message line, but still in the log.
+		assert!(messages.current_at(t0 + LIFETIME + Duration::from_secs(1)).is_none());
+		let texts: Vec<&str> = messages.log().map(|m| m.text.as_str()).collect();
+		assert_eq!(texts, ["hello", "careful"]);
+	}
+
+	#[test]
+	fn capacity() {
+		let mut messages = Messages::new();
+		for i in 0..CAPACITY + 10 {
+			messages.error(format!("error #{}", i));
+		}
+		assert_eq!(messages.log().len(), CAPACITY);
+		assert_eq!(messages.log().next().unwrap().text, "error #10");
+		assert_eq!(messages.current().unwrap().text, format!("error #{}", CAPACITY + 9));
+	}
+}
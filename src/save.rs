@@ -0,0 +1,266 @@
+/*
+ * Saving a game in progress to a file, and loading it back.
+ */
+
+use crate::grid::{Cell, Gem, Grid, Special, Stripes, MIN_COLOURS, MAX_COLOURS};
+use crate::score::Score;
+
+use ndarray::Array2;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// The first line of a save file. Bump the version whenever the format changes.
+const HEADER: &str = "bejeweled-term save 4";
+
+/// Everything needed to resume a game.
+///
+/// The file is plain text, one gem per word and one line of the grid per line:
+///
+///     bejeweled-term save 4
+///     size 2 3
+///     colours 6
+///     score 450 2
+///     cursor 1 0
+///     rng 1234 56
+///     R G- B
+///     Y* W# P
+///
+/// The score line holds the total, and the longest cascade of the game.
+/// The rng line holds the seed of the grid, and how far its random number generator went.
+/// The letter is the colour of the gem, and the symbol after it its special power if any.
+#[derive(Debug)]
+pub struct Save {
+	pub grid: Grid,
+	pub score: Score,
+	/// The cursor of the Term, (x, y) in the matrix convention.
+	pub cursor: (usize, usize),
+}
+
+/// The directory where the game keeps its files:
+/// $XDG_DATA_HOME/bejeweled-term, or ~/.local/share/bejeweled-term if that's not set.
+pub fn data_dir() -> Option<PathBuf> {
+	let base = match std::env::var_os("XDG_DATA_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+	};
+	Some(base.join("bejeweled-term"))
+}
+
+/// Where the game in progress is saved when the player quits.
+pub fn default_path() -> Option<PathBuf> {
+	data_dir().map(|dir| dir.join("save"))
+}
+
+impl Save {
+
+	pub fn write(&self, path: &Path) -> Result<(), &'static str> {
+		if let Some(dir) = path.parent() {
+			std::fs::create_dir_all(dir).map_err(|_| "Couldn't create the directory of the save file.")?;
+		}
+		std::fs::write(path, self.to_string()).map_err(|_| "Couldn't write the save file.")
+	}
+
+	pub fn read(path: &Path) -> Result<Save, &'static str> {
+		let s = std::fs::read_to_string(path).map_err(|_| "Couldn't read the save file.")?;
+		Save::parse(&s)
+	}
+
+	pub fn parse(s: &str) -> Result<Save, &'static str> {
+		let mut lines = s.lines();
+
+		if lines.next() != Some(HEADER) {
+			return Err("This is not a save file, or it comes from another version of the game.");
+		}
+
+		let size = field(lines.next(), "size")?;
+		let (height, width) = match size[..] {
+			[h, w] => (parse_usize(h)?, parse_usize(w)?),
+			_ => return Err("Bad size in the save file."),
+		};
+		if height < 2 || width < 2 || height > usize::from(u8::MAX) || width > usize::from(u8::MAX) {
+			return Err("Bad size in the save file.");
+		}
+
+		let colours = match field(lines.next(), "colours")?[..] {
+			[c] => c.parse::<u8>().map_err(|_| "Bad number of colours in the save file.")?,
+			_ => return Err("Bad number of colours in the save file."),
+		};
+		if !(MIN_COLOURS..=MAX_COLOURS).contains(&colours) {
+			return Err("Bad number of colours in the save file.");
+		}
+
+		let score = match field(lines.next(), "score")?[..] {
+			[total, combo] => Score::resume(
+				total.parse().map_err(|_| "Bad score in the save file.")?,
+				combo.parse().map_err(|_| "Bad score in the save file.")?,
+			),
+			_ => return Err("Bad score in the save file."),
+		};
+
+		let cursor = match field(lines.next(), "cursor")?[..] {
+			[x, y] => (parse_usize(x)?, parse_usize(y)?),
+			_ => return Err("Bad cursor in the save file."),
+		};
+		if cursor.0 >= height || cursor.1 >= width {
+			return Err("Bad cursor in the save file.");
+		}
+
+		let (seed, position) = match field(lines.next(), "rng")?[..] {
+			[seed, position] => (
+				seed.parse::<u64>().map_err(|_| "Bad seed in the save file.")?,
+				position.parse::<u128>().map_err(|_| "Bad random number generator in the save file.")?,
+			),
+			_ => return Err("Bad random number generator in the save file."),
+		};
+
+		let mut cells = Vec::with_capacity(height * width);
+		for _ in 0..height {
+			let line = lines.next().ok_or("The grid in the save file is too short.")?;
+			let len = cells.len();
+			for word in line.split_whitespace() {
+				cells.push(parse_cell(word)?);
+			}
+			if cells.len() - len != width {
+				return Err("The grid in the save file has a line of the wrong length.");
+			}
+		}
+
+		// a board of n colours only has the first n gems.
+		if cells.iter().any(|cell| cell.gem as u8 >= colours) {
+			return Err("The grid in the save file has more colours than it says.");
+		}
+
+		let cells = Array2::from_shape_vec((height, width), cells).map_err(|_| "Bad grid in the save file.")?;
+		let mut grid = Grid::from_cells(cells, colours, seed);
+		grid.set_rng_position(position);
+		return Ok(Save {
+			grid,
+			score,
+			cursor,
+		});
+	}
+}
+
+impl fmt::Display for Save {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{}", HEADER)?;
+		writeln!(f, "size {} {}", self.grid.lines(), self.grid.cols())?;
+		writeln!(f, "colours {}", self.grid.colours())?;
+		writeln!(f, "score {} {}", self.score.total(), self.score.best_combo())?;
+		writeln!(f, "cursor {} {}", self.cursor.0, self.cursor.1)?;
+		writeln!(f, "rng {} {}", self.grid.seed(), self.grid.rng_position())?;
+		for line in self.grid.cells.rows() {
+			let words: Vec<String> = line.iter().map(|&cell| cell_to_string(cell)).collect();
+			writeln!(f, "{}", words.join(" "))?;
+		}
+		Ok(())
+	}
+}
+
+/// Checks that the line starts with the given name, and returns the words after it.
+fn field<'a>(line: Option<&'a str>, name: &str) -> Result<Vec<&'a str>, &'static str> {
+	let mut words = line.ok_or("The save file is too short.")?.split_whitespace();
+	if words.next() != Some(name) {
+		return Err("A field is missing in the save file.");
+	}
+	Ok(words.collect())
+}
+
+fn parse_usize(s: &str) -> Result<usize, &'static str> {
+	s.parse().map_err(|_| "Bad number in the save file.")
+}
+
+fn cell_to_string(cell: Cell) -> String {
+	let gem = match cell.gem {
+		Gem::Green => 'G',
+		Gem::Red => 'R',
+		Gem::Yellow => 'Y',
+		Gem::Blue => 'B',
+		Gem::White => 'W',
+		Gem::Pink => 'P',
+		Gem::Cyan => 'C',
+	};
+	let special = match cell.special {
+		Special::None => "",
+		Special::Striped(Stripes::Horizontal) => "-",
+		Special::Striped(Stripes::Vertical) => "|",
+		Special::Bomb => "*",
+		Special::Hypercube => "#",
+	};
+	format!("{}{}", gem, special)
+}
+
+fn parse_cell(word: &str) -> Result<Cell, &'static str> {
+	let mut chars = word.chars();
+	let gem = match chars.next() {
+		Some('G') => Gem::Green,
+		Some('R') => Gem::Red,
+		Some('Y') => Gem::Yellow,
+		Some('B') => Gem::Blue,
+		Some('W') => Gem::White,
+		Some('P') => Gem::Pink,
+		Some('C') => Gem::Cyan,
+		_ => return Err("Unknown gem in the save file."),
+	};
+	let special = match chars.as_str() {
+		"" => Special::None,
+		"-" => Special::Striped(Stripes::Horizontal),
+		"|" => Special::Striped(Stripes::Vertical),
+		"*" => Special::Bomb,
+		"#" => Special::Hypercube,
+		_ => return Err("Unknown special gem in the save file."),
+	};
+	Ok(Cell { gem, special })
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let mut grid = Grid::new_rand(5, 4, 5, 99).unwrap();
+		grid.gravity(&[(2, 2)]);
+		grid.cells[[1, 2]].special = Special::Striped(Stripes::Vertical);
+		grid.cells[[4, 0]].special = Special::Hypercube;
+		grid.cells[[0, 3]].special = Special::Bomb;
+		let save = Save { grid, score: Score::resume(4225, 3), cursor: (4, 3) };
+
+		let text = save.to_string();
+		let loaded = Save::parse(&text).unwrap();
+		assert_eq!(loaded.grid.cells, save.grid.cells);
+		assert_eq!(loaded.grid.seed(), 99);
+		assert_eq!(loaded.grid.colours(), 5);
+		assert_eq!(loaded.grid.rng_position(), save.grid.rng_position());
+		assert_eq!(loaded.score.total(), 4225);
+		assert_eq!(loaded.score.best_combo(), 3);
+		assert_eq!(loaded.cursor, (4, 3));
+		assert_eq!(loaded.to_string(), text);
+	}
+
+	#[test]
+	fn bad_files() {
+		let good = "bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 1 0\nrng 5 0\nR G- B\nY* W# P\n";
+		let save = Save::parse(good).unwrap();
+		assert_eq!(save.grid.cell(0, 1), Cell { gem: Gem::Green, special: Special::Striped(Stripes::Horizontal) });
+
+		for bad in &[
+			"",
+			"bejeweled-term save 3\nsize 2 3\ncolours 7\nscore 4.5\ncursor 1 0\nR G- B\nY* W# P\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 2 0\nrng 5 0\nR G- B\nY* W# P\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 1 0\nrng 5 0\nR G- B\nY* W#\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 1 0\nrng 5 0\nR G- B\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 1 0\nrng 5 0\nR G- B\nY* W# Q\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 1 0\nrng 5 0\nR G+ B\nY* W# P\n",
+			"bejeweled-term save 4\nsize 1 3\ncolours 7\nscore 450 2\ncursor 0 0\nrng 5 0\nR G B\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 450 2\ncursor 1 0\nrng -5 0\nR G- B\nY* W# P\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 9\nscore 450 2\ncursor 1 0\nrng 5 0\nR G- B\nY* W# P\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 5\nscore 450 2\ncursor 1 0\nrng 5 0\nR G- B\nY* W# P\n",
+			"bejeweled-term save 4\nsize 2 3\ncolours 7\nscore 4.5\ncursor 1 0\nrng 5 0\nR G- B\nY* W# P\n",
+			"bejeweled-term save 4\nscore 450 2\nsize 2 3\ncolours 7\ncursor 1 0\nrng 5 0\nR G- B\nY* W# P\n",
+		] {
+			assert!(Save::parse(bad).is_err(), "{:?}", bad);
+		}
+	}
+}
@@ -0,0 +1,166 @@
+/*
+ * The scoring rules, and the running total.
+ */
+
+use crate::grid::{Crush, Special};
+
+use std::fmt;
+
+// Every matched gem is worth that much.
+const POINTS_PER_GEM: u64 = 10;
+
+/// Bonus for a match longer than three gems.
+fn long_match_bonus(len: u8) -> u64 {
+	match len {
+		0..=3 => 0,
+		4 => 20,
+		_ => 50 * u64::from(len - 4),
+	}
+}
+
+/// Bonus for a special gem going off.
+fn special_bonus(special: Special) -> u64 {
+	match special {
+		Special::None => 0,
+		Special::Striped(_) => 30,
+		Special::Bomb => 50,
+		Special::Hypercube => 100,
+	}
+}
+
+/// The points of a single move, cascades included.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Breakdown {
+	/// Points of the matched gems, before the multiplier.
+	pub matched: u64,
+	/// Bonus of the matches of four and more, before the multiplier.
+	pub long: u64,
+	/// Bonus of the special gems that went off, before the multiplier.
+	pub special: u64,
+	/// How many steps the cascade had. The n-th step is worth n times its points.
+	pub combo: u32,
+	/// The points of the move, multipliers included.
+	pub points: u64,
+}
+
+impl fmt::Display for Breakdown {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "+{} ({} matched", self.points, self.matched)?;
+		if self.long != 0 {
+			write!(f, ", {} long", self.long)?;
+		}
+		if self.special != 0 {
+			write!(f, ", {} special", self.special)?;
+		}
+		write!(f, ")")?;
+		if self.combo > 1 {
+			write!(f, " combo ×{}", self.combo)?;
+		}
+		Ok(())
+	}
+}
+
+/// Keeps the running total of a game.
+///
+/// Feed it every step of a move with crush(), then call end_move() once the cascade is over.
+#[derive(Clone, Debug, Default)]
+pub struct Score {
+	total: u64,
+	best_combo: u32,
+	current: Breakdown,
+	last: Option<Breakdown>,
+}
+
+impl Score {
+
+	/// A score that starts from an earlier game.
+	pub fn resume(total: u64, best_combo: u32) -> Score {
+		Score { total, best_combo, ..Default::default() }
+	}
+
+	/// Counts the points of one step of the move. `level` is 0 for the step triggered by the swap,
+	/// and goes up by one at each step of the cascade.
+	/// Returns the points of that step, multiplier included.
+	pub fn crush(&mut self, level: u32, crush: &Crush) -> u64 {
+		let matched: u64 = crush.matches.iter().map(|&(_, len)| u64::from(len) * POINTS_PER_GEM).sum();
+		let long: u64 = crush.matches.iter().map(|&(_, len)| long_match_bonus(len)).sum();
+		let special: u64 = crush.detonated.iter().map(|&s| special_bonus(s)).sum();
+		let points = (matched + long + special) * u64::from(level + 1);
+
+		self.current.matched += matched;
+		self.current.long += long;
+		self.current.special += special;
+		self.current.combo = u32::max(self.current.combo, level + 1);
+		self.current.points += points;
+		self.total += points;
+		return points;
+	}
+
+	/// Closes the current move and returns its breakdown.
+	pub fn end_move(&mut self) -> Breakdown {
+		let b = std::mem::take(&mut self.current);
+		self.best_combo = u32::max(self.best_combo, b.combo);
+		self.last = Some(b);
+		return b;
+	}
+
+	pub fn total(&self) -> u64 {
+		self.total
+	}
+
+	/// The longest cascade of the game.
+	pub fn best_combo(&self) -> u32 {
+		self.best_combo
+	}
+
+	/// The breakdown of the last move, if there was one.
+	pub fn last(&self) -> Option<Breakdown> {
+		self.last
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::grid::Gem::*;
+	use crate::grid::Stripes;
+
+	#[test]
+	fn points() {
+		let mut score = Score::default();
+
+		let first = Crush {
+			matches: vec![(Red, 3), (Blue, 4)],
+			detonated: vec![Special::Striped(Stripes::Vertical)],
+			..Default::default()
+		};
+		assert_eq!(score.crush(0, &first), 30 + 40 + 20 + 30);
+
+		let second = Crush {
+			matches: vec![(Green, 5), (Green, 3)],
+			detonated: vec![Special::Bomb],
+			..Default::default()
+		};
+		assert_eq!(score.crush(1, &second), (50 + 30 + 50 + 50) * 2);
+
+		let b = score.end_move();
+		assert_eq!(b, Breakdown {
+			matched: 30 + 40 + 50 + 30,
+			long: 20 + 50,
+			special: 30 + 50,
+			combo: 2,
+			points: 120 + 360,
+		});
+		assert_eq!(score.total(), 480);
+		assert_eq!(score.best_combo(), 2);
+		assert_eq!(score.last(), Some(b));
+		assert_eq!(b.to_string(), "+480 (150 matched, 70 long, 80 special) combo ×2");
+
+		// The next move starts from scratch, but the total goes on.
+		score.crush(0, &Crush { matches: vec![(Cyan, 3)], ..Default::default() });
+		let b = score.end_move();
+		assert_eq!(b.to_string(), "+30 (30 matched)");
+		assert_eq!(score.total(), 510);
+		assert_eq!(score.best_combo(), 2);
+	}
+}
@@ -2,252 +2,675 @@
  * The view handles what we see on the terminal.
  */
 
+use crate::engine::{Ending, Event, GameState, Outcome, Refusal, Status};
+use crate::frontend::{self, Colour, Frontend, Glyph, Input, Style};
 use crate::grid;
-
-use ncurses::*;
-
-use std::convert::TryInto;
-use std::sync::atomic::{AtomicBool, Ordering};
-
-static NCURSES_FLAG: AtomicBool = AtomicBool::new(false); // true if NCurses is active
-static NCURSES_LOCK: AtomicBool = AtomicBool::new(false); // lock for NCurses' critical section
+use crate::keys::{Action, Direction, Key, KeyMap};
+use crate::message::{Messages, Severity};
+
+use std::time::{Duration, Instant};
+
+// The log pane is only drawn if it can be at least that wide.
+const LOG_MIN_WIDTH: usize = 20;
+// How long a frame of the animations lasts.
+const FRAME: Duration = Duration::from_millis(30);
+
+/// How the gems look.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Theme {
+	/// Solid blocks of colour.
+	Classic,
+	/// Blocks of colour with the initial of the colour in their middle, for those who have
+	/// a hard time telling the colours apart.
+	Letters,
+}
 
 /// Terminal handler/wrapper, the piece of data that controls the terminal. Graphics and user input.
 /// It used to be named Tui, for terminal user interface, in my previous
 /// attempt. I kind of miss that special name, but it'd be confusing.
 ///
-/// NCurses is unsafe and the terminal it controls is a global state. So any function that deals with
-/// ncurses requires a &mut Term argument so that Rust's borrow checker will prevent a concurrent
-/// call on any function that deals with ncurses.
-#[derive(Debug)]
+/// It draws and reads the keys through a Frontend, which holds the terminal itself.
 pub struct Term {
+	screen: Box<dyn Frontend>,
 	gem_width: u8,
 	gem_height: u8,
 	gap_width: u8, // "gap" is the empty space between tiles/gems.
 	gap_height: u8,
+	theme: Theme,
 	cursor_y: u8, // bad. to change
 	cursor_x: u8,
-
-	pub msg: &'static str
+	input_timeout: Option<Duration>, // None means blocking input.
+	last_input: Instant,
+	message_shown: bool, // whether the last draw() showed a message, that may have expired since.
+	log_scroll: usize, // how many messages the log pane is scrolled back.
+	mouse_pressed: Option<(u8, u8)>, // the tile the mouse button went down on, for drags.
+	mouse_selected: Option<(u8, u8)>, // the tile clicked first, waiting for a neighbour to be clicked.
+	picked: Option<(u8, u8)>, // the gem picked up with the keyboard, the next direction swaps it.
+
+	/// What happened lately. The newest message shows under the grid, all of them in the log pane.
+	pub messages: Messages,
+	/// Shown under the grid when there's no message.
+	pub help: String,
+	/// What the keys do.
+	pub keys: KeyMap,
+	/// Whether to show the log pane on the right of the grid, if there is room for it.
+	pub log_visible: bool,
+	/// The lines of the status bar, under the message.
+	pub status: Vec<String>,
+	/// Two tiles to highlight, to help a player who is stuck.
+	pub hint: Option<grid::Move>,
+	/// Leave the keys pressed during an announce to the caller instead of flushing them,
+	/// for the autoplay, that any key stops.
+	pub keep_input: bool,
 }
 
 impl Term {
-	pub fn new() -> Result<Term, &'static str> {
-
-		// Spin on the lock to handle NCurses, as long as "true" was stored,
-		while NCURSES_LOCK.swap(true, Ordering::SeqCst) == true {}
-		// Test and set
-		let f = NCURSES_FLAG.swap(true, Ordering::SeqCst);
-		if f == true {
-			NCURSES_LOCK.store(false, Ordering::SeqCst);
-			return Err("NCurses was already initialized by another Tui.");
-		}
-
-		let r = init_ncurses();
-		NCURSES_LOCK.store(false, Ordering::SeqCst); // end of NCurses' critical section
-		if r.is_err() {
-			NCURSES_FLAG.store(false, Ordering::SeqCst);
-			return Err("NCurses could not be initialized correctly.");
-		}
-
-
-
-		return Ok(Term{
+	pub fn new(theme: Theme, screen: Box<dyn Frontend>) -> Term {
+		return Term{
+				screen,
 				gem_width: 4,
 				gem_height: 2,
 				gap_width: 2,
 				gap_height: 1,
+				theme,
 				cursor_y: 0,
 				cursor_x: 0,
-				msg: "Press 'w' to exit."
-		});
+				input_timeout: None,
+				last_input: Instant::now(),
+				message_shown: false,
+				log_scroll: 0,
+				mouse_pressed: None,
+				mouse_selected: None,
+				picked: None,
+				messages: Messages::new(),
+				help: KeyMap::default().help("save and exit"),
+				keys: KeyMap::default(),
+				log_visible: true,
+				status: Vec::new(),
+				hint: None,
+				keep_input: false,
+		};
+	}
+
+	/// Make process_input() give up waiting for a key after the given delay, so that the caller
+	/// gets the control back from time to time. None restores the blocking behaviour.
+	pub fn set_input_timeout(&mut self, delay: Option<Duration>) {
+		self.input_timeout = delay;
+	}
+
+	/// How long it's been since the user last pressed a key.
+	pub fn idle_time(&self) -> Duration {
+		self.last_input.elapsed()
 	}
 
 	/// Test whether there is enough space to draw a grid in the current terminal.
 	/// Calling Term::draw() in too small a terminal is not an error and prints a message instead.
 	pub fn can_draw(&self, g: &grid::Grid) -> bool {
-		g.cols()*usize::from(self.gem_width) + (g.cols() - 1)*usize::from(self.gap_width) <= COLS() as usize
-		&&
-		g.lines()*usize::from(self.gem_height) + (g.lines() - 1)*usize::from(self.gap_height) + 2 <= LINES() as usize
+		let (lines, cols) = self.board_size(g);
+		let (screen_lines, screen_cols) = self.screen.size();
+		cols <= screen_cols && lines + 2 + self.status.len() <= screen_lines
+	}
+
+	/// How many lines and columns of the screen the grid takes.
+	fn board_size(&self, g: &grid::Grid) -> (usize, usize) {
+		(
+			g.lines()*usize::from(self.gem_height) + (g.lines() - 1)*usize::from(self.gap_height),
+			g.cols()*usize::from(self.gem_width) + (g.cols() - 1)*usize::from(self.gap_width),
+		)
 	}
 
 	pub fn draw(&mut self, grid: &grid::Grid) {
-		erase();
+		self.render(grid);
+		self.screen.refresh();
+	}
+
+	/// Draw everything, like draw(), but leave it to the caller to refresh() the screen once
+	/// they have drawn their own things on top of it.
+	/// Returns false if the screen was too small to draw the grid.
+	fn render(&mut self, grid: &grid::Grid) -> bool {
+		self.screen.erase();
+		let (screen_lines, width) = self.screen.size();
 
 		if ! self.can_draw(grid) {
-			mvaddstr(LINES() / 2, 0, "The screen is too smol UwU");
-			refresh();
-			return;
+			self.screen.put_str(screen_lines as i32 / 2, 0, "The screen is too smol UwU", Style::default());
+			return false;
+		}
+
+		for x in 0..grid.lines() {
+			for y in 0..grid.cols() {
+				self.fill_tile(x, y, tile(grid.cell(x, y)));
+			}
+		}
+		// the newest message, or some help, under the board and its gap.
+		let (board_lines, _) = self.board_size(grid);
+		let line = (board_lines + usize::from(self.gap_height)) as i32;
+		let message = self.messages.current();
+		self.message_shown = message.is_some();
+		match message {
+			Some(m) => self.screen.put_str(line, 0, &m.text, severity_style(m.severity)),
+			None => self.screen.put_str(line, 0, &self.help, Style::default().dim()),
+		}
+		// the status bar, in reverse video all across the screen.
+		for (i, text) in self.status.iter().enumerate() {
+			self.screen.put_str(line + 1 + i as i32, 0, &format!("{:<1$.1$}", text, width), Style::default().reverse());
 		}
 
-		let grid_height = grid.lines();
-		let grid_width = grid.cols();
-
-		// this function may change. A lot can be done to improve it. Se the comment
-
-		wmove(stdscr(), 0, 0);
-		for y in 0..grid_height {
-			// draw self.gem_height colorful lines.
-			for _ in 0..self.gem_height {
-				for x in 0..grid_width {
-					// draw the gem
-					let c = ' ' as chtype | A_REVERSE() | color(grid.get(y, x));
-					for _ in 0..self.gem_width {
-						addch(c); // could write a char array instead...
-					}
-					// draw the gap
-					for _ in 0..self.gap_width {
-						addch(' ' as chtype); // could do a wmove() instead.
-					}
+		if self.theme == Theme::Letters {
+			for ((x, y), cell) in grid.cells.indexed_iter() {
+				if cell.special == grid::Special::None {
+					let (line, col) = self.tile_position(x, y);
+					let middle = (line + i32::from(self.gem_height / 2), col + i32::from(self.gem_width / 2));
+					self.screen.put(middle.0, middle.1, Glyph::new(letter(cell.gem), Style::colour(colour(cell.gem)).bold().reverse()));
 				}
-				// new line
-				addch('\n' as chtype);
 			}
-			// the gap
-			for _ in 0..self.gap_height {
-				addch('\n' as chtype);
+		}
+
+		if self.log_visible {
+			self.draw_log(grid);
+		}
+
+		// highlight the hint, if any, with a pattern in place of the plain colour.
+		if let Some((a, b)) = self.hint {
+			for &(x, y) in &[a, b] {
+				self.fill_tile(x, y, Glyph::new(frontend::CHECKER, Style::colour(colour(grid.get(x, y))).bold()));
 			}
 		}
-		// temporary
-		addstr(self.msg);
 
 		// draw the 'cursor', it highlights the current tile.
-		let r = self.echo_cursor(grid, self.cursor_y, self.cursor_x, self.cursor_y, self.cursor_x);
-		if let Err(e) = r {
-			self.msg = e;
-			addstr(self.msg);
-		}
-
-		refresh();
-	}
-
-	fn echo_cursor(&mut self,
-		           grid: &grid::Grid,
-		           old_y: u8,
-				   old_x: u8,
-				   new_y: u8,
-				   new_x: u8) -> Result<(), &'static str> {
-		let mut error = false;
-		// Erase the previous cursor
-		let y = old_y * (self.gem_height + self.gap_height);
-		let x = old_x * (self.gem_width  + self.gap_width);
-		error |= ERR == wmove(stdscr(), y.into(), x.into());
-		error |= ERR == echochar (32 | A_REVERSE() | color(grid.get(old_y.into(),old_x.into())));
-		// Echo the new one.
-		let y = new_y * (self.gem_height + self.gap_height);
-		let x = new_x * (self.gem_width  + self.gap_width);
-		error |= ERR == wmove(stdscr(), y.into(), x.into());
-		error |= ERR == echochar (ACS_DIAMOND() | A_REVERSE() | color(grid.get(new_y.into(), new_x.into())));
-
-		if error {
-			Err("Error when drawing the cursor. Probably due to position outside of bounds.")
-		} else {
-			Ok(())
-		}
-	}
-
-	/// some refractor wouldn't hurt.
-	pub fn process_input(&mut self, g: &grid::Grid) -> Option<i32> {
-		// fetch user's input.
-		// TODO if it's a weird char (eg å) then it won't fetch all the bytes.
-		// look in the commented main() dead code below to see how to suck up all the bytes.
-		let c = getch();
-		// if it's a KEY, then with compute the new cursor position here.
-		let new_cursor: Option<(u8, u8)> = match c {
-			KEY_UP => {
-				if self.cursor_y != 0 {
-					Some((
-						self.cursor_y - 1,
-						self.cursor_x,
-					))
-				} else {
-					Some((
-						g.lines() as u8 - 1,
-						self.cursor_x,
-					))
-				}
+		self.draw_cursor(grid);
+		return true;
+	}
+
+	/// How many frames an animation of that duration has, 0 if it shouldn't be played at all.
+	fn frames(&self, grid: &grid::Grid, duration: Duration) -> u32 {
+		if ! self.can_draw(grid) {
+			return 0;
+		}
+		return (duration.as_millis() / FRAME.as_millis()) as u32;
+	}
+
+	/// Slide the gems at `a` and `b` into each other's place. The grid shows them already swapped.
+	pub fn animate_swap(&mut self, grid: &grid::Grid, a: (usize, usize), b: (usize, usize), duration: Duration) {
+		let frames = self.frames(grid, duration);
+		for f in 0..frames {
+			let t = f as f32 / frames as f32;
+			self.render(grid);
+			for &(to, from) in &[(a, b), (b, a)] {
+				self.fill_tile(to.0, to.1, Glyph::default());
+				let (line0, col0) = self.tile_position(from.0, from.1);
+				let (line1, col1) = self.tile_position(to.0, to.1);
+				let line = line0 + ((line1 - line0) as f32 * t).round() as i32;
+				let col = col0 + ((col1 - col0) as f32 * t).round() as i32;
+				self.draw_tile_at(line, col, grid.cell(to.0, to.1));
 			}
-			KEY_LEFT => {
-				if self.cursor_x != 0 {
-					Some((
-						self.cursor_y,
-						self.cursor_x - 1,
-					))
+			self.screen.refresh();
+			self.screen.sleep(FRAME);
+		}
+	}
+
+	/// Play one step of a turn: the destroyed gems flash and fade out of the `before` grid,
+	/// then the gems of the `after` grid fall in their place, the new ones from above the board.
+	/// The flash takes a third of the duration, the fall the rest.
+	pub fn animate_crush(&mut self, before: &grid::Grid, after: &grid::Grid, crush: &grid::Crush, duration: Duration) {
+		let frames = self.frames(before, duration / 3);
+		for f in 0..frames {
+			self.render(before);
+			for &(x, y) in &crush.destroyed {
+				let (x, y) = (usize::from(x), usize::from(y));
+				let c = if f + 2 >= frames {
+					Glyph::default() // faded out
+				} else if f % 4 < 2 {
+					Glyph::new(frontend::CHECKER, Style::colour(colour(before.get(x, y))).bold())
 				} else {
-					Some((
-						self.cursor_y,
-						g.cols() as u8 - 1,
-					))
+					Glyph::new('*', Style::colour(Colour::White).bold().reverse())
+				};
+				self.fill_tile(x, y, c);
+			}
+			self.screen.refresh();
+			self.screen.sleep(FRAME);
+		}
+
+		let frames = self.frames(after, duration - duration / 3);
+		let pitch = i32::from(self.gem_height + self.gap_height);
+		for f in 0..frames {
+			let t = f as f32 / frames as f32;
+			self.render(after);
+			self.clear_board(after);
+			for ((x, y), &fall) in crush.fall.drops(after.size()).indexed_iter() {
+				let (line, col) = self.tile_position(x, y);
+				let distance = i32::from(fall) * pitch;
+				let line = line - distance + (distance as f32 * t).round() as i32;
+				self.draw_tile_at(line, col, after.cell(x, y));
+			}
+			self.screen.refresh();
+			self.screen.sleep(FRAME);
+		}
+	}
+
+	/// The line and column of the screen where the tile (x, y) starts.
+	fn tile_position(&self, x: usize, y: usize) -> (i32, i32) {
+		(
+			(x * usize::from(self.gem_height + self.gap_height)) as i32,
+			(y * usize::from(self.gem_width + self.gap_width)) as i32,
+		)
+	}
+
+	/// Draw a tile anywhere on the screen, in pixels so to speak. The lines above the screen
+	/// are left out, so that the gems can come down from out of the board.
+	fn draw_tile_at(&mut self, line: i32, col: i32, cell: grid::Cell) {
+		let c = tile(cell);
+		for l in line..line + i32::from(self.gem_height) {
+			for i in 0..i32::from(self.gem_width) {
+				self.screen.put(l, col + i, c);
+			}
+		}
+		if self.theme == Theme::Letters && cell.special == grid::Special::None {
+			let middle = (line + i32::from(self.gem_height / 2), col + i32::from(self.gem_width / 2));
+			self.screen.put(middle.0, middle.1, Glyph::new(letter(cell.gem), Style::colour(colour(cell.gem)).bold().reverse()));
+		}
+	}
+
+	/// Blank the whole area of the board.
+	fn clear_board(&mut self, grid: &grid::Grid) {
+		let (lines, cols) = self.board_size(grid);
+		for line in 0..lines {
+			self.screen.put_str(line as i32, 0, &" ".repeat(cols), Style::default());
+		}
+	}
+
+	/// Draw the log pane on the right of the grid, the newest messages at the bottom.
+	/// Nothing is drawn if the screen isn't wide enough.
+	fn draw_log(&mut self, grid: &grid::Grid) {
+		let (height, board_width) = self.board_size(grid);
+		let left = board_width + usize::from(self.gap_width);
+		let width = self.screen.size().1.saturating_sub(left + 2);
+		if width < LOG_MIN_WIDTH {
+			return;
+		}
+
+		// the separator
+		for line in 0..height {
+			self.screen.put(line as i32, left as i32, Glyph::new(frontend::VLINE, Style::default()));
+		}
+
+		// as many messages as there are lines, scrolled back by log_scroll.
+		let log = self.messages.log();
+		let skip = usize::min(self.log_scroll, log.len());
+		let messages: Vec<_> = log.rev().skip(skip).take(height).collect();
+		for (i, m) in messages.iter().enumerate() {
+			let t = self.messages.timestamp(m).as_secs();
+			let text = format!("{:02}:{:02} {}", t / 60, t % 60, m.text);
+			let text = format!("{:.1$}", text, width);
+			self.screen.put_str((height - 1 - i) as i32, (left + 2) as i32, &text, severity_style(m.severity));
+		}
+	}
+
+	/// Scroll the log pane back in time by `n` messages, or forward if `n` is negative.
+	fn scroll_log(&mut self, n: isize) {
+		let max = self.messages.log().len().saturating_sub(1);
+		self.log_scroll = usize::min(self.log_scroll.saturating_add_signed(n), max);
+	}
+
+	/// Overwrite the whole surface of the tile (x, y) with the given character.
+	fn fill_tile(&mut self, x: usize, y: usize, c: Glyph) {
+		let (top, left) = self.tile_position(x, y);
+		for line in top..top + i32::from(self.gem_height) {
+			for col in left..left + i32::from(self.gem_width) {
+				self.screen.put(line, col, c);
+			}
+		}
+	}
+
+	/// Mark the tile under the cursor with a diamond in its corner. A picked up gem is covered
+	/// in diamonds.
+	fn draw_cursor(&mut self, grid: &grid::Grid) {
+		let (x, y) = self.get_cursor();
+		let style = Style::colour(colour(grid.get(x, y))).reverse();
+		if self.picked == Some((self.cursor_y, self.cursor_x)) {
+			self.fill_tile(x, y, Glyph::new(frontend::DIAMOND, style.bold()));
+		} else {
+			let (line, col) = self.tile_position(x, y);
+			self.screen.put(line, col, Glyph::new(frontend::DIAMOND, style));
+		}
+	}
+
+	/// Wait for a key, or until the input timeout, and return what the player wants to do.
+	/// The cursor moves, the log scrolls and the screen is resized here, the game isn't bothered
+	/// with them.
+	pub fn process_input(&mut self, g: &grid::Grid) -> Option<Action> {
+		let Some(input) = self.screen.input(self.input_timeout) else {
+			if self.message_shown && self.messages.current().is_none() {
+				// The message expired while waiting, take it off the screen.
+				self.draw(g);
+			}
+			return None;
+		};
+		self.last_input = Instant::now();
+		if let Input::Mouse { line, col, pressed } = input {
+			return self.process_mouse(g, line, col, pressed);
+		}
+		self.mouse_selected = None;
+
+		let key = match input {
+			Input::Key(key) => key,
+			Input::Resize => {
+				self.draw(g);
+				return None;
+			}
+			Input::PageUp | Input::PageDown => {
+				self.scroll_log(if input == Input::PageUp {5} else {-5});
+				self.draw(g);
+				return None;
+			}
+			Input::Mouse { .. } | Input::Unknown => return None,
+		};
+
+		match self.keys.action(key) {
+			// with a gem picked up, the cursor keys swap it.
+			Some(Action::Cursor(d)) if self.picked.is_some() => return self.swap(g, d),
+			Some(Action::Cursor(d)) => {
+				self.move_cursor(g, d);
+				return None;
+			}
+			Some(Action::Select) if self.picked.is_none() => {
+				self.picked = Some((self.cursor_y, self.cursor_x));
+				self.draw(g);
+				return None;
+			}
+			Some(Action::Select) | Some(Action::Cancel) => {
+				self.put_down(g);
+				return None;
+			}
+			Some(Action::Swap(d)) => return self.swap(g, d),
+			action => return action,
+		}
+	}
+
+	/// Swap the gem under the cursor with its neighbour in that direction. If there's none on
+	/// that side, the player is told, and the gem they picked up stays up.
+	fn swap(&mut self, g: &grid::Grid, d: Direction) -> Option<Action> {
+		if d.neighbour(self.get_cursor(), g.size()).is_none() {
+			self.messages.warn("There's no gem on that side.");
+			self.draw(g);
+			return None;
+		}
+		self.put_down(g);
+		return Some(Action::Swap(d));
+	}
+
+	/// Wait that long, unless the player presses a key or clicks in the meantime.
+	/// Returns whether they did. The autoplay stops on any key.
+	pub fn interrupted(&mut self, g: &grid::Grid, wait: Duration) -> bool {
+		let end = Instant::now() + wait;
+		loop {
+			let timeout = end.saturating_duration_since(Instant::now());
+			match self.screen.input(Some(timeout)) {
+				None => return false,
+				Some(Input::Key(_) | Input::Mouse { pressed: true, .. }) => {
+					self.last_input = Instant::now();
+					return true;
 				}
+				Some(Input::Resize) => self.draw(g),
+				Some(_) => (),
 			}
-			KEY_DOWN => {
-				if usize::from(self.cursor_y) != g.lines() - 1 {
-					Some((
-						self.cursor_y + 1,
-						self.cursor_x,
-					))
+		}
+	}
+
+	/// Put the picked up gem back down, if there is one.
+	fn put_down(&mut self, g: &grid::Grid) {
+		if self.picked.take().is_some() {
+			self.draw(g);
+		}
+	}
+
+	/// Move the cursor to the next tile in that direction, going round the edges of the board.
+	fn move_cursor(&mut self, g: &grid::Grid, d: Direction) {
+		let (lines, cols) = (g.lines() as u8, g.cols() as u8);
+		let (y, x) = (self.cursor_y, self.cursor_x);
+		(self.cursor_y, self.cursor_x) = match d {
+			Direction::Up => (if y == 0 {lines - 1} else {y - 1}, x),
+			Direction::Left => (y, if x == 0 {cols - 1} else {x - 1}),
+			Direction::Down => (if y + 1 == lines {0} else {y + 1}, x),
+			Direction::Right => (y, if x + 1 == cols {0} else {x + 1}),
+		};
+		self.draw(g);
+	}
+
+	/// Shows what happened in the game, with its animations. Each step of a cascade takes
+	/// `delay`, a swap a quarter of it. The banner at the end of the game is up to
+	/// the caller.
+	pub fn show(&mut self, state: &GameState, event: &Event, delay: Duration) {
+		match event {
+			Event::Swapped { grid, a, b, matched } => {
+				self.animate_swap(grid, *a, *b, delay / 4);
+				if *matched {
+					// The board is about to change, the hint is now meaningless.
+					self.hint = None;
 				} else {
-					Some ((
-						0,
-						self.cursor_x,
-					))
+					let mut back = grid.as_ref().clone();
+					back.permute(*a, *b);
+					self.animate_swap(&back, *a, *b, delay / 4);
+					self.messages.warn("No match!");
 				}
 			}
-			KEY_RIGHT => {
-				if usize::from(self.cursor_x) != g.cols() - 1 {
-					Some((
-						self.cursor_y,
-						self.cursor_x + 1,
-					))
-				} else {
-					Some((
-						self.cursor_y,
-						0,
-					))
+			Event::Crushed { before, after, crush, status } => {
+				self.status = status_lines(status);
+				self.animate_crush(before, after, crush, delay);
+			}
+			Event::Combo { combo, bonus: Some(bonus) } => self.messages.info(format!("Combo ×{}! +{}s", combo, bonus.as_secs())),
+			Event::Combo { combo, bonus: None } => self.messages.info(format!("Combo ×{}!", combo)),
+			Event::Scored(b) => {
+				if b.special != 0 {
+					self.messages.info(format!("Boom! {}", b));
 				}
 			}
-			KEY_RESIZE => {
-				erase();
-				self.draw(g);
-				None
+			Event::Reshuffled => self.messages.warn("No move left on the board! It was reshuffled."),
+			Event::Hint(hint) => self.hint = *hint,
+			Event::Undone | Event::Redone => {
+				self.hint = None;
+				self.messages.info(if let Event::Undone = event {"Move undone."} else {"Move redone."});
+			}
+			Event::Refused(why) => self.messages.warn(refusal(*why)),
+			Event::LevelUp(next) => {
+				self.hint = None;
+				self.set_cursor((0, 0));
+				self.status = status_lines(&state.status());
+				self.announce(state.grid(), &[
+					format!("Level {}!", next.number),
+					format!("{}×{} board, {} colours", next.height, next.width, next.colours),
+					format!("Next level at {} points", next.target),
+				]);
+				self.messages.info(format!("Welcome to level {}.", next.number));
+			}
+			Event::Restarted => {
+				self.hint = None;
+				self.set_cursor((0, 0));
+				self.messages.info("New game, good luck!");
 			}
-			_ => None
+			Event::Over(ending) if ending.outcome() == Outcome::Lost => self.messages.warn(title(*ending)),
+			Event::Over(ending) => self.messages.info(title(*ending)),
+		}
+	}
+
+	/// Lay out the status bar. Returns whether it changed.
+	pub fn set_status(&mut self, status: &Status) -> bool {
+		let lines = status_lines(status);
+		let changed = lines != self.status;
+		self.status = lines;
+		return changed;
+	}
+
+	/// Show the board with a banner on top of it, and wait for the player to press a key.
+	/// The first line is the title of the banner, in green if the player won and red if they lost.
+	/// Returns the key that was pressed, None if the game doesn't know that one.
+	pub fn game_over(&mut self, grid: &grid::Grid, outcome: Outcome, lines: &[String]) -> Option<Key> {
+		// The player was likely typing when the game ended, don't let a stray key
+		// dismiss the banner before it is even seen.
+		self.screen.sleep(Duration::from_millis(500));
+		self.screen.flush_input();
+		let title = match outcome {
+			Outcome::Won => Style::colour(Colour::Green).bold(),
+			Outcome::Lost => Style::colour(Colour::Red).bold(),
+			Outcome::Finished => Style::default().bold(),
 		};
-		// phew, that's a big match
-
-		if let Some((new_y, new_x)) = new_cursor { // The cursor shall move.
-			let r = self.echo_cursor(
-				g,
-				self.cursor_y,
-				self.cursor_x,
-				new_y,
-				new_x,
-			);
-			if let Err(s) = r {
-				self.msg = s;
-				return None;
+		return self.wait_on_banner(grid, lines, title);
+	}
+
+	/// Show the board with a banner on top of it, until the player presses a key.
+	/// The first line is the title of the banner.
+	pub fn popup(&mut self, grid: &grid::Grid, lines: &[String]) {
+		self.wait_on_banner(grid, lines, Style::default().bold());
+		self.draw(grid);
+	}
+
+	fn wait_on_banner(&mut self, grid: &grid::Grid, lines: &[String], title: Style) -> Option<Key> {
+		let input = loop {
+			self.render(grid);
+			self.draw_banner(lines, title);
+			self.screen.refresh();
+			match self.screen.input(None) {
+				Some(Input::Resize) => (),
+				input => break input,
 			}
-			// Then we update the cursor position in the data.
-			self.cursor_y = new_y;
-			self.cursor_x = new_x;
+		};
+		self.last_input = Instant::now();
+		match input {
+			Some(Input::Key(key)) => return Some(key),
+			_ => return None,
+		}
+	}
+
+	/// Show the board with a banner on top of it, and let the caller decide how long it stays.
+	pub fn show_banner(&mut self, grid: &grid::Grid, lines: &[String]) {
+		self.render(grid);
+		self.draw_banner(lines, Style::default().bold());
+		self.screen.refresh();
+	}
+
+	/// Show a banner on top of the board for a little while, then let the game go on.
+	/// It opens up from the middle of the screen, then its title blinks.
+	pub fn announce(&mut self, grid: &grid::Grid, lines: &[String]) {
+		let inner = banner_width(lines);
+		for width in (2..inner).step_by(2) {
+			self.render(grid);
+			self.draw_frame(lines.len() + 4, width);
+			self.screen.refresh();
+			self.screen.sleep(Duration::from_millis(15));
+		}
+		for i in 0..6 {
+			self.render(grid);
+			let title = Style::default().bold();
+			self.draw_banner(lines, if i % 2 == 0 {title.reverse()} else {title});
+			self.screen.refresh();
+			self.screen.sleep(Duration::from_millis(250));
+		}
+		// Don't let the keys pressed in the meantime move the cursor on the new board.
+		if !self.keep_input {
+			self.screen.flush_input();
+		}
+		self.draw(grid);
+	}
+
+	/// Draw a frame in the middle of the screen with the given lines in it, the first one with
+	/// the `title` style.
+	fn draw_banner(&mut self, lines: &[String], title: Style) {
+		let inner = banner_width(lines);
+		let (top, left) = self.draw_frame(lines.len() + 4, inner);
+		for (i, text) in lines.iter().enumerate() {
+			let style = if i == 0 {title} else {Style::default()};
+			self.screen.put_str(top + 2 + i as i32, left + 1, &format!("{:^1$}", text, inner), style);
+		}
+	}
+
+	/// Draw an empty frame `inner` columns wide inside, in the middle of the screen.
+	/// Returns the line and column of its top left corner.
+	fn draw_frame(&mut self, height: usize, inner: usize) -> (i32, i32) {
+		let width = inner + 2;
+		let (lines, cols) = self.screen.size();
+		let top = lines.saturating_sub(height) / 2;
+		let left = cols.saturating_sub(width) / 2;
+		let (top, left, bottom, right) = (top as i32, left as i32, (top + height - 1) as i32, (left + width - 1) as i32);
+
+		// the frame, blank inside.
+		let line = |c| Glyph::new(c, Style::default());
+		for col in left + 1..right {
+			self.screen.put(top, col, line(frontend::HLINE));
+			self.screen.put(bottom, col, line(frontend::HLINE));
+		}
+		for l in top + 1..bottom {
+			self.screen.put(l, left, line(frontend::VLINE));
+			self.screen.put(l, right, line(frontend::VLINE));
+			self.screen.put_str(l, left + 1, &" ".repeat(inner), Style::default());
+		}
+		self.screen.put(top, left, line(frontend::ULCORNER));
+		self.screen.put(top, right, line(frontend::URCORNER));
+		self.screen.put(bottom, left, line(frontend::LLCORNER));
+		self.screen.put(bottom, right, line(frontend::LRCORNER));
+		return (top, left);
+	}
+
+	/// Handle a mouse event. A click puts the cursor on the tile, a click on a neighbour of the
+	/// tile clicked just before swaps them, and so does a drag from a tile to its neighbour.
+	/// A swap is returned as a swap from the cursor, put on the first tile, as if it had been
+	/// made with the keyboard.
+	fn process_mouse(&mut self, g: &grid::Grid, line: i32, col: i32, pressed: bool) -> Option<Action> {
+		let tile = self.tile_at(g, line, col);
+
+		if pressed {
+			self.mouse_pressed = tile;
 			return None;
+		}
+		// ncurses may swallow the press of a quick click, then it's a click on the released tile.
+		let (pressed, tile) = match (self.mouse_pressed.take().or(tile), tile) {
+			(Some(p), Some(t)) => (p, t),
+			_ => return None, // outside of the board.
+		};
+		let (from, to) = if pressed != tile {
+			(pressed, tile) // dragged
 		} else {
-			// The user didn't move so we return the character he pressed.
-
-			let byte: Result<u8, _> = c.try_into();
-			if let Ok(byte) = byte {
-				if byte.is_ascii() {
-					return Some(c);
-				} else {
-					empty_stdin(self);
+			// a gem picked up with the keyboard counts as a click.
+			match self.mouse_selected.take().or(self.picked.take()) {
+				Some(s) if s != tile => (s, tile),
+				_ => {
+					// first click, just select the tile.
+					self.mouse_selected = Some(tile);
+					self.set_cursor((tile.0.into(), tile.1.into()));
+					self.draw(g);
 					return None;
 				}
-			} else { // This case is reached if the character fetched was e.g. an arrow key.
+			}
+		};
+		self.mouse_selected = None;
+		self.set_cursor((from.0.into(), from.1.into()));
+		self.draw(g);
+		match Direction::between((from.0.into(), from.1.into()), (to.0.into(), to.1.into())) {
+			Some(d) => return Some(Action::Swap(d)),
+			None => {
+				// not a neighbour, the second tile is the new selection.
+				self.mouse_selected = Some(to);
+				self.set_cursor((to.0.into(), to.1.into()));
+				self.draw(g);
 				return None;
 			}
 		}
 	}
 
+	/// The tile (x, y) under the given line and column of the screen, if it's not in a gap.
+	fn tile_at(&self, g: &grid::Grid, line: i32, col: i32) -> Option<(u8, u8)> {
+		if line < 0 || col < 0 {
+			return None;
+		}
+		let (line, col) = (line as usize, col as usize);
+		let pitch_y = usize::from(self.gem_height + self.gap_height);
+		let pitch_x = usize::from(self.gem_width + self.gap_width);
+		let (x, y) = (line / pitch_y, col / pitch_x);
+		if line % pitch_y >= usize::from(self.gem_height) || col % pitch_x >= usize::from(self.gem_width)
+		   || x >= g.lines() || y >= g.cols() {
+			return None;
+		}
+		return Some((x as u8, y as u8));
+	}
+
 	/// Return the coordinates of the currently selected tile (or gem).
 	/// It is (x, y) in the matrix convention.
 	///
@@ -256,164 +679,313 @@ impl Term {
 		return (self.cursor_y.into(), self.cursor_x.into());
 	}
 
+	/// Move the cursor to the tile (x, y), in the matrix convention. Used when resuming a game.
+	pub fn set_cursor(&mut self, (x, y): (usize, usize)) {
+		self.cursor_y = x as u8;
+		self.cursor_x = y as u8;
+	}
+
 }
 
-impl Drop for Term {
-	fn drop(&mut self) {
-		free_ncurses();
+fn colour(gem: grid::Gem) -> Colour {
+	match gem {
+		grid::Gem::Green => Colour::Green,
+		grid::Gem::Red => Colour::Red,
+		grid::Gem::Yellow => Colour::Yellow,
+		grid::Gem::Blue => Colour::Blue,
+		grid::Gem::White => Colour::White,
+		grid::Gem::Pink => Colour::Magenta,
+		grid::Gem::Cyan => Colour::Cyan,
 	}
 }
 
-/// Suck up all the bytes in the buffer of the standard input throught NCurses's getch()
-/// and return control once the buffer is empty. Panic if it deems the buffer was too full.
-fn empty_stdin(_ncurses_guard: &mut Term) {
-	// Non-blocking mode.
-	if nodelay(stdscr(), true) == ERR {
-		endwin();
-		panic!("Couldn't set no-delay mode for stdin.");
-	}
-	for _ in 0..64 {
-		if getch() == ERR {
-			// Ok, the input buffer is empty, we can exit the function.
-			// Back to blocking mode.
-			if nodelay(stdscr(), false) == ERR {
-				endwin();
-				panic!("Couldn't set yes-delay mode for stdin.");
-			}
-			return;
-		}
+/// The character a tile is filled with. Plain gems are solid blocks of colour, special gems have
+/// a pattern drawn on them.
+fn tile(cell: grid::Cell) -> Glyph {
+	let style = Style::colour(colour(cell.gem)).reverse();
+	match cell.special {
+		grid::Special::None => Glyph::new(' ', style),
+		grid::Special::Striped(grid::Stripes::Horizontal) => Glyph::new(frontend::HLINE, style),
+		grid::Special::Striped(grid::Stripes::Vertical) => Glyph::new(frontend::VLINE, style),
+		grid::Special::Bomb => Glyph::new('*', style.bold()),
+		// The colour of a hypercube is of no interest to the player, it is always white.
+		grid::Special::Hypercube => Glyph::new(frontend::LANTERN, Style::colour(Colour::White).bold().reverse()),
 	}
-	// If this part is reached, it means we should crash because the buffer was too full.
-	// it is a conservative security feature. The user input is not trusted.
-	panic!("There are too many characters in the stdin buffer yo. Aborting. Wtf were u doing.");
 }
 
-// pub fn main() -> Result<i32, ()> {
-// 	init_ncurses()?;
-// 	addstr("Press 'q' to quit\n");
-// 	loop {
-// 		// Set blocking
-// 		if nodelay(stdscr(), false) == ERR {
-// 			endwin();
-// 			return Err(());
-// 		}
-// 		let mut c = getch();
-// 		if c == 'q' as i32 || c == ERR {
-// 			break;
-// 		}
-// 		// Set non-blocking
-// 		if nodelay(stdscr(), true) == ERR {
-// 			endwin();
-// 			return Err(());
-// 		}
-// //TODO add a compter to crash if the user provided 36 characters in one click. But it's buffered
-// 		let mut vec = vec![0u8; 4];
-// 		'tag: for i in 1..4 {
-// 			let second = getch();
-// 			if second == ERR {break 'tag;}
-//
-// 			let byte: u8 = second.try_into().unwrap_or_else(|_| {
-// 						endwin(); panic!("that char was bigger than a c char")});
-//
-// 			// unsafe {
-// 			// 	let pointer = (&mut c as *mut i32).cast::<u8>();
-// 			// 	pointer.add(i).write(byte);
-// 			// }
-// 			vec[i] = byte;
-// 		}
-//
-// 		if 0 < c && c < 255 {
-// 			vec[0] = (c & 0x000000FF) as u8;
-// 		}
-// 		let s = String::from_utf8(vec).unwrap_or_else(|e|{
-// 			let mut s = String::new();
-// 			write!(&mut s, "{}", e).unwrap();
-// 			return s;
-// 		});
-// 		let ch = s.chars().next();
-// 		let mut string = String::new();
-// 		writeln!(&mut string, "{} (unicode: {:?})", c, ch).unwrap();
-// 		addstr(&string[..]);
-// 	}
-//
-//
-// 	endwin();
-// 	return Ok('F' as i32);
-// }
-
-
-// fn sub_window() -> Result<WINDOW, ()> {
-// 	let w = subwin(stdscr(), 10, 10, 10, 10);
-// 	if w.is_null() {return Err(());}
-//
-// 	mvwaddch(w, 0,0, 'B' as chtype | A_BOLD() | A_UNDERLINE() | COLOR_PAIR(3) | A_REVERSE());
-// 	touchwin(stdscr());
-// 	wrefresh(w);
-// 	Ok(w)
-// }
-
-/// Uninitialize NCurses. Thread-safe.
-/// Call before the standard panic to ensure NCurses is freed. Otherwise the panic message may land
-/// on NCurses' screen.
-pub fn free_ncurses() {
-	// spin-lock
-	while NCURSES_LOCK.swap(true, Ordering::SeqCst) {}
-	if NCURSES_FLAG.swap(false, Ordering::SeqCst) {
-		endwin(); // The flag was "true" so NCurses is active and needs to be uninitialized.
-		// NCURSES_FLAG is now false, whatever state it was in before.
-	}
-	// release the spin-lock
-	NCURSES_LOCK.store(false, Ordering::SeqCst);
+/// The initial of the colour of the gem, for the letters theme.
+fn letter(gem: grid::Gem) -> char {
+	match gem {
+		grid::Gem::Green => 'G',
+		grid::Gem::Red => 'R',
+		grid::Gem::Yellow => 'Y',
+		grid::Gem::Blue => 'B',
+		grid::Gem::White => 'W',
+		grid::Gem::Pink => 'P',
+		grid::Gem::Cyan => 'C',
+	}
 }
 
-fn init_ncurses() -> Result<(), ()> {
-	let r = initscr(); // Initializes stuff and put the terminal in that screen mode.
-	if r.is_null() {
-		eprintln!("Failed to initialize the terminal interface. Aborting.");
-		return Err(());
-	}
-	let mut r = false;
-	r |= ERR == cbreak(); // No char buffering. NCurses sucks typed chars immediately.
-	r |= ERR == noecho(); // Do not print the typed character on the terminal.
-	r |= ERR == keypad(stdscr(), true); // Allow capture of the arrow keys and others.
-	r |= ERR == nonl(); // Turn '\n' into '\r' when printed. "no newline". Not useful but anyway.
-	// r |= clearok(stdscr(), false); ???
-	curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE); // invisible cursor
-	if r {
-		endwin();
-		eprintln!("sh!t happened: ncurses was not correctly initialized.");
-		return Err(());
-	}
-	if !has_colors() {
-		endwin();
-		eprintln!("No color! You can't play with no color, exiting.");
-		return Err(());
-	}
-	start_color();
-	wmove(stdscr(), 0, 0);
-
-	init_pair(1, COLOR_WHITE, COLOR_BLACK);
-	init_pair(2, COLOR_RED, COLOR_BLACK);
-	init_pair(3, COLOR_GREEN, COLOR_BLACK);
-	init_pair(4, COLOR_YELLOW, COLOR_BLACK);
-	init_pair(5, COLOR_BLUE, COLOR_BLACK);
-	init_pair(6, COLOR_MAGENTA, COLOR_BLACK);
-	init_pair(7, COLOR_CYAN, COLOR_BLACK);
-	init_pair(8, COLOR_BLACK, COLOR_WHITE);
-
-	erase(); // ensure the screen starts blank.
-
-	Ok(())
+/// How many columns the inside of a banner with these lines takes.
+fn banner_width(lines: &[String]) -> usize {
+	lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 2
 }
 
-fn color(gem: grid::Gem) -> chtype {
-	match gem {
-		grid::Gem::Green => COLOR_PAIR(3),
-		grid::Gem::Red => COLOR_PAIR(2),
-		grid::Gem::Yellow => COLOR_PAIR(4),
-		grid::Gem::Blue => COLOR_PAIR(5),
-		grid::Gem::White => COLOR_PAIR(1),
-		grid::Gem::Pink => COLOR_PAIR(6),
-		grid::Gem::Cyan => COLOR_PAIR(7),
+/// The lines of the status bar: the limits of the game first, then the score and the seed,
+/// and what the last move was worth under them.
+fn status_lines(status: &Status) -> Vec<String> {
+	let mut first = format!("Score: {}  Best combo: ×{}  Seed: {}", status.score, status.best_combo, status.seed);
+	if let Some(level) = status.level {
+		first.insert_str(0, &format!("Level: {}  Next: {}  ", level.number, level.target));
+	}
+	if let Some((left, target)) = status.moves {
+		first.insert_str(0, &format!("Moves: {}  Target: {}  ", left, target));
+	}
+	if let Some(time) = status.time {
+		first.insert_str(0, &format!("Time: {}  ", clock(time)));
+	}
+	let last = match status.last {
+		Some(b) => format!("Last move: {}", b),
+		None => String::from("Last move: none yet"),
+	};
+	vec![first, last]
+}
+
+/// Time left, as m:ss. Rounded up, so that the clock reads 0:00 only when the time is up.
+fn clock(left: Duration) -> String {
+	let secs = left.as_millis().div_ceil(1000);
+	format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// The title of the game over banner.
+pub fn title(ending: Ending) -> &'static str {
+	match ending {
+		Ending::TimeUp => "Time's up!",
+		Ending::TargetReached => "Target reached!",
+		Ending::OutOfMoves => "Out of moves!",
+	}
+}
+
+fn refusal(why: Refusal) -> &'static str {
+	match why {
+		Refusal::GameOver => "The game is over.",
+		Refusal::NotNeighbours => "Only neighbours can be swapped.",
+		Refusal::NothingToUndo => "Nothing to undo.",
+		Refusal::NothingToRedo => "Nothing to redo.",
+	}
+}
+
+/// How a message of the given severity is highlighted.
+fn severity_style(severity: Severity) -> Style {
+	match severity {
+		Severity::Info => Style::default(),
+		Severity::Warning => Style::colour(Colour::Yellow).bold(),
+		Severity::Error => Style::colour(Colour::Red).bold(),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::frontend::memory::Memory;
+
+	fn term(theme: Theme, size: (usize, usize)) -> (Term, Memory) {
+		let memory = Memory::new(size);
+		let mut term = Term::new(theme, Box::new(memory.clone()));
+		term.status = vec![String::from("Score: 0  Seed: 42"), String::from("Last move: none yet")];
+		return (term, memory);
+	}
+
+	/// Compares the screen with the golden file tests/screens/NAME.txt. Run the tests with
+	/// UPDATE_SCREENS=1 to write the files anew, then review the changes.
+	fn golden(memory: &Memory, name: &str) {
+		let path = format!("{}/tests/screens/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+		let snapshot = memory.snapshot();
+		if std::env::var_os("UPDATE_SCREENS").is_some() {
+			std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+			std::fs::write(&path, &snapshot).unwrap();
+		}
+		let expected = std::fs::read_to_string(&path)
+		                  .unwrap_or_else(|_| panic!("no {}, run the tests with UPDATE_SCREENS=1", path));
+		assert!(snapshot == expected, "the screen isn't {}:\n{}", path, snapshot);
+	}
+
+	#[test]
+	fn board() {
+		let grid = grid::Grid::new_rand(8, 6, 7, 42).unwrap();
+		let (mut term, memory) = term(Theme::Letters, (30, 80));
+		term.draw(&grid);
+		assert!(term.can_draw(&grid));
+		golden(&memory, "board");
+	}
+
+	#[test]
+	fn cursor() {
+		let grid = grid::Grid::new_rand(4, 4, 5, 7).unwrap();
+		let (mut term, memory) = term(Theme::Classic, (20, 30));
+		memory.push(Input::Key(Key::Arrow(Direction::Right)));
+		memory.push(Input::Key(Key::Arrow(Direction::Up)));
+		memory.push(Input::Key(Key::Enter));
+		for _ in 0..3 {
+			assert_eq!(term.process_input(&grid), None);
+		}
+		// wrapped round the top of the board, and picked up the gem there.
+		assert_eq!(term.get_cursor(), (3, 1));
+		golden(&memory, "cursor");
+
+		// then an arrow swaps it, and puts it down.
+		memory.push(Input::Key(Key::Arrow(Direction::Left)));
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Left)));
+		// a click on a tile moves the cursor there, a click on its neighbour swaps them.
+		memory.push(Input::Mouse { line: 3, col: 7, pressed: true });
+		memory.push(Input::Mouse { line: 3, col: 7, pressed: false });
+		memory.push(Input::Mouse { line: 6, col: 7, pressed: true });
+		memory.push(Input::Mouse { line: 6, col: 7, pressed: false });
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.get_cursor(), (1, 1));
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Down)));
+		// the other keys go to the game.
+		memory.type_text("zl");
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Up)));
+		assert_eq!(term.process_input(&grid), Some(Action::Log));
+		// no input left, the time ran out.
+		assert_eq!(term.process_input(&grid), None);
+	}
+
+	#[test]
+	fn too_small() {
+		let grid = grid::Grid::new_rand(8, 6, 7, 42).unwrap();
+		let (mut term, memory) = term(Theme::Classic, (10, 30));
+		term.draw(&grid);
+		assert!(!term.can_draw(&grid));
+		golden(&memory, "too_small");
+	}
+
+	#[test]
+	fn pick_up() {
+		let grid = grid::Grid::new_rand(4, 4, 5, 7).unwrap();
+		let (mut term, memory) = term(Theme::Classic, (20, 30));
+		// in the top left corner, there's nothing above the gem.
+		memory.push(Input::Key(Key::Enter));
+		memory.push(Input::Key(Key::Arrow(Direction::Up)));
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.picked, Some((0, 0)));
+		assert_eq!(term.messages.current().unwrap().text, "There's no gem on that side.");
+		// it's still in hand, the next arrow swaps it and puts it down.
+		memory.push(Input::Key(Key::Arrow(Direction::Right)));
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Right)));
+		assert_eq!(term.picked, None);
+		// so do the swap keys.
+		memory.push(Input::Key(Key::Enter));
+		memory.type_text("qd");
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.picked, Some((0, 0)));
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Right)));
+		assert_eq!(term.picked, None);
+	}
+
+	#[test]
+	fn announce() {
+		let grid = grid::Grid::new_rand(4, 4, 5, 7).unwrap();
+		let (mut term, memory) = term(Theme::Classic, (20, 30));
+		// the keys typed during the banner are dropped, unless the caller wants them.
+		memory.push(Input::Key(Key::Char('x')));
+		term.announce(&grid, &[String::from("Level 2!")]);
+		assert!(!term.interrupted(&grid, Duration::ZERO));
+		term.keep_input = true;
+		memory.push(Input::Key(Key::Char('x')));
+		term.announce(&grid, &[String::from("Level 2!")]);
+		assert!(term.interrupted(&grid, Duration::ZERO));
+	}
+
+	#[test]
+	fn mouse() {
+		let grid = grid::Grid::new_rand(4, 4, 5, 7).unwrap();
+		let (mut term, memory) = term(Theme::Classic, (20, 30));
+		// the tiles are 3 lines and 6 columns apart.
+		let click = |x: i32, y: i32| {
+			memory.push(Input::Mouse { line: 3 * x, col: 6 * y, pressed: true });
+			memory.push(Input::Mouse { line: 3 * x, col: 6 * y, pressed: false });
+		};
+
+		// a gem dragged onto its neighbour.
+		memory.push(Input::Mouse { line: 6, col: 12, pressed: true });
+		memory.push(Input::Mouse { line: 6, col: 18, pressed: false });
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Right)));
+		assert_eq!(term.get_cursor(), (2, 2));
+
+		// the clicks in the gaps do nothing.
+		memory.push(Input::Mouse { line: 2, col: 0, pressed: true });
+		memory.push(Input::Mouse { line: 2, col: 0, pressed: false });
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.mouse_selected, None);
+
+		// a click on a gem that isn't a neighbour selects that one instead.
+		click(0, 0);
+		click(2, 2);
+		click(1, 2);
+		for _ in 0..5 {
+			assert_eq!(term.process_input(&grid), None);
+		}
+		assert_eq!(term.mouse_selected, Some((2, 2)));
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Up)));
+		assert_eq!(term.get_cursor(), (2, 2));
+		assert_eq!(term.mouse_selected, None);
+
+		// without their press, the releases are clicks.
+		memory.push(Input::Mouse { line: 0, col: 0, pressed: false });
+		memory.push(Input::Mouse { line: 0, col: 6, pressed: false });
+		assert_eq!(term.process_input(&grid), None);
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Right)));
+		assert_eq!(term.get_cursor(), (0, 0));
+
+		// a gem picked up with the keyboard is swapped with the one clicked next to it.
+		memory.push(Input::Key(Key::Enter));
+		click(1, 0);
+		for _ in 0..2 {
+			assert_eq!(term.process_input(&grid), None);
+		}
+		assert_eq!(term.process_input(&grid), Some(Action::Swap(Direction::Down)));
+		assert_eq!(term.picked, None);
+	}
+
+	#[test]
+	fn status() {
+		let grid = grid::Grid::new_rand(3, 5, 4, 1).unwrap();
+		let (mut term, memory) = term(Theme::Classic, (14, 60));
+		term.messages.info("Welcome back!");
+		term.messages.warn("No match!");
+		term.status[0] = String::from("Moves: 3  Target: 1000  Score: 420  Best combo: ×2  Seed: 1, and some more to cut");
+		term.hint = Some(((0, 0), (0, 1)));
+		term.draw(&grid);
+		golden(&memory, "status");
+
+		// the message expires, the help takes its place.
+		term.messages = Messages::new();
+		term.log_visible = false;
+		term.draw(&grid);
+		assert!(memory.text().contains(&term.help));
+		assert!(!memory.text().contains("No match!"));
+		assert!(memory.refreshes() >= 2);
+	}
+
+	#[test]
+	fn status_bar() {
+		let mut status = Status { score: 420, best_combo: 2, seed: 1, last: None, time: None, moves: None, level: None };
+		assert_eq!(status_lines(&status), ["Score: 420  Best combo: ×2  Seed: 1", "Last move: none yet"]);
+		status.moves = Some((3, 1000));
+		status.time = Some(Duration::from_millis(59_001));
+		assert_eq!(status_lines(&status)[0], "Time: 1:00  Moves: 3  Target: 1000  Score: 420  Best combo: ×2  Seed: 1");
+
+		assert_eq!(clock(Duration::from_secs(60)), "1:00");
+		assert_eq!(clock(Duration::from_millis(9_500)), "0:10");
+		assert_eq!(clock(Duration::from_millis(1)), "0:01");
+		assert_eq!(clock(Duration::ZERO), "0:00");
 	}
 }